@@ -0,0 +1,242 @@
+//! Preferences and window layout kept between runs.
+//!
+//! They are stored as `key = value` lines, such as `theme = light`, in `wavespy.conf` in the
+//! user's configuration directory, which is `~/.config/wavespy` unless `XDG_CONFIG_HOME` says
+//! otherwise. Each `recent` line holds a recently opened dump, most recent first. Lines starting
+//! with `#` are comments.
+
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use gtk::glib;
+use vcd::TimescaleUnit;
+
+use crate::rows::{Density, Radix};
+use crate::theme::Theme;
+use crate::util::invalid_data;
+use crate::wave_viewer::DEFAULT_FONT;
+
+pub static DENSITIES: &[(&str, Density)] = &[
+    ("compact", Density::Compact),
+    ("normal", Density::Normal),
+    ("tall", Density::Tall),
+];
+/// `auto` shows times in the unit of the timescale of the dump.
+pub static TIME_UNITS: &[(&str, Option<TimescaleUnit>)] = &[
+    ("auto", None),
+    ("s", Some(TimescaleUnit::S)),
+    ("ms", Some(TimescaleUnit::MS)),
+    ("us", Some(TimescaleUnit::US)),
+    ("ns", Some(TimescaleUnit::NS)),
+    ("ps", Some(TimescaleUnit::PS)),
+    ("fs", Some(TimescaleUnit::FS)),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Name of a built-in theme.
+    pub theme: String,
+    pub font: String,
+    /// Radix of newly added rows.
+    pub radix: Radix,
+    pub density: Density,
+    pub time_unit: Option<TimescaleUnit>,
+    /// Whether the most recent dump is opened at startup.
+    pub reopen_last: bool,
+    /// Most recent first.
+    pub recent_files: Vec<PathBuf>,
+    pub recent_limit: usize,
+    pub window_size: (i32, i32),
+    /// Width of the signal finder.
+    pub finder_width: i32,
+    /// Width of the name column, and of the name and value columns together.
+    pub column_widths: (i32, i32),
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            theme: "dark".to_string(),
+            font: DEFAULT_FONT.to_string(),
+            radix: Radix::default(),
+            density: Density::default(),
+            time_unit: None,
+            reopen_last: false,
+            recent_files: vec![],
+            recent_limit: 10,
+            window_size: (1200, 600),
+            finder_width: 250,
+            column_widths: (100, 200),
+        }
+    }
+}
+
+impl Config {
+    pub fn filename() -> PathBuf {
+        glib::user_config_dir().join("wavespy").join("wavespy.conf")
+    }
+
+    /// Reads a config file. Lines that are not valid are skipped, and the errors they cause are
+    /// returned with the config made of the other lines.
+    pub fn load(filename: &Path) -> Result<(Self, Vec<Error>), Error> {
+        Ok(Config::parse(&fs::read_to_string(filename)?))
+    }
+
+    pub fn parse(text: &str) -> (Self, Vec<Error>) {
+        let mut config = Config::default();
+        let mut errors = vec![];
+        for (lineno, line) in text.lines().enumerate() {
+            let lineno = lineno + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                errors.push(invalid_data(lineno, "expected 'key = value'"));
+                continue;
+            };
+            if config.set(key.trim(), value.trim()).is_none() {
+                errors.push(invalid_data(lineno, "unknown key or invalid value"));
+            }
+        }
+        (config, errors)
+    }
+
+    pub fn save(&self, filename: &Path) -> Result<(), Error> {
+        if let Some(dir) = filename.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(filename, self.to_text())
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str(&format!("theme = {}\n", self.theme));
+        text.push_str(&format!("font = {}\n", self.font));
+        text.push_str(&format!("radix = {}\n", self.radix.name()));
+        text.push_str(&format!(
+            "density = {}\n",
+            name_of(DENSITIES, &self.density)
+        ));
+        text.push_str(&format!(
+            "time-unit = {}\n",
+            name_of(TIME_UNITS, &self.time_unit)
+        ));
+        let reopen_last = if self.reopen_last { "yes" } else { "no" };
+        text.push_str(&format!("reopen-last = {}\n", reopen_last));
+        text.push_str(&format!("recent-limit = {}\n", self.recent_limit));
+        for path in &self.recent_files {
+            text.push_str(&format!("recent = {}\n", path.display()));
+        }
+        let (width, height) = self.window_size;
+        text.push_str(&format!("window-size = {},{}\n", width, height));
+        text.push_str(&format!("finder-width = {}\n", self.finder_width));
+        let (names, columns) = self.column_widths;
+        text.push_str(&format!("column-widths = {},{}\n", names, columns));
+        text
+    }
+
+    /// Moves `filename` to the top of the recent files.
+    pub fn add_recent(&mut self, filename: &Path) {
+        self.recent_files.retain(|path| path != filename);
+        self.recent_files.insert(0, filename.to_path_buf());
+        self.recent_files.truncate(self.recent_limit);
+    }
+
+    /// Applies one `key = value` line, or returns `None` if it is not valid.
+    fn set(&mut self, key: &str, value: &str) -> Option<()> {
+        match key {
+            "theme" => {
+                let (name, _) = Theme::NAMES.iter().find(|(name, _)| *name == value)?;
+                self.theme = name.to_string();
+            }
+            "font" => self.font = value.to_string(),
+            "radix" => self.radix = Radix::from_name(value)?,
+            "density" => self.density = by_name(DENSITIES, value)?,
+            "time-unit" => self.time_unit = by_name(TIME_UNITS, value)?,
+            "reopen-last" => {
+                self.reopen_last = match value {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return None,
+                }
+            }
+            "recent-limit" => self.recent_limit = value.parse().ok()?,
+            "recent" => self.recent_files.push(PathBuf::from(value)),
+            "window-size" => self.window_size = pair(value)?,
+            "finder-width" => self.finder_width = value.parse().ok()?,
+            "column-widths" => self.column_widths = pair(value)?,
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+fn by_name<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, value)| *value)
+}
+
+fn name_of<T: PartialEq>(table: &[(&'static str, T)], value: &T) -> &'static str {
+    table
+        .iter()
+        .find(|(_, v)| v == value)
+        .map_or("", |(name, _)| name)
+}
+
+/// Parses two numbers separated by a comma, such as `1200,600`.
+fn pair(value: &str) -> Option<(i32, i32)> {
+    let (a, b) = value.split_once(',')?;
+    Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let config = Config {
+            theme: "high-contrast".to_string(),
+            font: "Sans Bold 12".to_string(),
+            radix: Radix::Signed,
+            density: Density::Tall,
+            time_unit: Some(TimescaleUnit::NS),
+            reopen_last: true,
+            recent_files: vec![PathBuf::from("/tmp/b.vcd"), PathBuf::from("a b.vcd")],
+            recent_limit: 3,
+            window_size: (800, 400),
+            finder_width: 120,
+            column_widths: (90, 180),
+        };
+        let (parsed, errors) = Config::parse(&config.to_text());
+        assert!(errors.is_empty());
+        assert_eq!(parsed, config);
+        let (parsed, errors) = Config::parse(&Config::default().to_text());
+        assert!(errors.is_empty());
+        assert_eq!(parsed, Config::default());
+    }
+
+    #[test]
+    fn invalid_lines_are_skipped() {
+        let text = "# comment\n\
+                    \n\
+                    theme = light\n\
+                    theme = sepia\n\
+                    density\n\
+                    colour = red\n\
+                    recent-limit = -1\n\
+                    window-size = 640,480\n";
+        let (config, errors) = Config::parse(text);
+        let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("line 4"), "{}", lines[0]);
+        assert!(lines[3].starts_with("line 7"), "{}", lines[3]);
+        assert_eq!(config.theme, "light");
+        assert_eq!(config.window_size, (640, 480));
+        assert_eq!(config.recent_limit, Config::default().recent_limit);
+    }
+}
@@ -0,0 +1,964 @@
+//! Expressions over signals, used for derived signals such as `valid & ready`, `addr[11:4]`,
+//! `{hi, lo}` or `count + 1`.
+//!
+//! Values are four-state bit vectors as in Verilog. Bitwise operators work bit by bit, while
+//! arithmetic and comparisons give X if any operand bit is X or Z, as do `/` and `%` by zero.
+//! Unsized numbers are as wide as needed and binary operators extend the narrower operand with
+//! zeros.
+//!
+//! Real and string variables keep their type. Arithmetic, comparisons and logical operators on
+//! a real work in floating point, and strings can be compared with literals such as `"IDLE"`.
+
+use std::collections::HashMap;
+
+use vcd::Value;
+
+use crate::wave_viewer::{vector_bit, WaveChangePoint, WaveData, WaveValue};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    LogicalNot,
+    Negate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Mod,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Xor,
+    Or,
+    LogicalAnd,
+    LogicalOr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A signal named by its full path, such as `top.cpu.pc`.
+    Signal(String),
+    Const(ExprValue),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// Bits `[msb:lsb]`, numbered as declared for signals and from 0 at the LSB otherwise.
+    Slice(Box<Expr>, i64, i64),
+    Concat(Vec<Expr>),
+}
+
+/// A four-state bit vector, most significant bit first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bits(pub Vec<Value>);
+
+impl Bits {
+    fn unknown(width: usize) -> Bits {
+        Bits(vec![Value::X; width])
+    }
+
+    fn from_u128(n: u128, width: usize) -> Bits {
+        Bits(
+            (0..width)
+                .rev()
+                .map(|i| {
+                    if i < 128 && (n >> i) & 1 == 1 {
+                        Value::V1
+                    } else {
+                        Value::V0
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn from_bool(b: Option<bool>) -> Bits {
+        Bits(vec![match b {
+            Some(true) => Value::V1,
+            Some(false) => Value::V0,
+            None => Value::X,
+        }])
+    }
+
+    pub fn width(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The value as an unsigned number, or `None` if it has X or Z bits or does not fit.
+    pub fn to_u128(&self) -> Option<u128> {
+        let mut n: u128 = 0;
+        for v in &self.0 {
+            n = n.checked_mul(2)?;
+            match v {
+                Value::V0 => (),
+                Value::V1 => n += 1,
+                _ => return None,
+            }
+        }
+        Some(n)
+    }
+
+    /// True if any bit is 1, false if all bits are 0, and unknown otherwise.
+    fn truth(&self) -> Option<bool> {
+        if self.0.contains(&Value::V1) {
+            Some(true)
+        } else if self.0.iter().all(|v| *v == Value::V0) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Zero-extends or truncates on the MSB side.
+    fn resize(&self, width: usize) -> Bits {
+        let len = self.width();
+        if width >= len {
+            let mut bits = vec![Value::V0; width - len];
+            bits.extend_from_slice(&self.0);
+            Bits(bits)
+        } else {
+            Bits(self.0[len - width..].to_vec())
+        }
+    }
+
+    fn to_wave_value(&self) -> WaveValue {
+        match self.0[..] {
+            [v] => WaveValue::Scalar(v),
+            _ => WaveValue::Vector(self.0.iter().copied().collect()),
+        }
+    }
+}
+
+/// The value of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprValue {
+    Bits(Bits),
+    Real(f64),
+    Str(String),
+}
+
+impl ExprValue {
+    /// The value as bits. As in Verilog, reals are truncated to a 64-bit integer and strings
+    /// have 8 bits per character.
+    fn bits(&self) -> Bits {
+        match self {
+            ExprValue::Bits(bits) => bits.clone(),
+            ExprValue::Real(r) if r.is_finite() => Bits::from_u128(*r as i64 as u128, 64),
+            ExprValue::Real(_) => Bits::unknown(64),
+            ExprValue::Str(s) => Bits(
+                s.bytes()
+                    .flat_map(|b| Bits::from_u128(b as u128, 8).0)
+                    .collect(),
+            ),
+        }
+    }
+
+    fn real(&self) -> Option<f64> {
+        match self {
+            ExprValue::Real(r) => Some(*r),
+            _ => self.bits().to_u128().map(|n| n as f64),
+        }
+    }
+
+    fn truth(&self) -> Option<bool> {
+        match self {
+            ExprValue::Real(r) => Some(*r != 0.0),
+            _ => self.bits().truth(),
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.bits().width()
+    }
+
+    fn to_wave_value(&self) -> WaveValue {
+        match self {
+            ExprValue::Bits(bits) => bits.to_wave_value(),
+            ExprValue::Real(r) => WaveValue::Real(*r),
+            ExprValue::Str(s) => WaveValue::String(s.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(ExprValue),
+    Str(String),
+    Op(&'static str),
+}
+
+/// Operators and punctuation, longest first so that `<=` is not read as `<`.
+static OPERATORS: [&str; 27] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "&", "|", "^", "~", "!", "+", "-", "*", "/",
+    "%", "<", ">", "(", ")", "[", "]", "{", "}", ":",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        if c.is_ascii_alphabetic() || c == '_' || c == '\\' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "_$.\\".contains(c)))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else if c.is_ascii_digit() || c == '\'' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "'_.".contains(c)))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..len])?));
+            rest = &rest[len..];
+        } else if c == '"' {
+            let len = rest[1..]
+                .find('"')
+                .ok_or_else(|| "unterminated string".to_string())?;
+            tokens.push(Token::Str(rest[1..len + 1].to_string()));
+            rest = &rest[len + 2..];
+        } else if c == ',' {
+            tokens.push(Token::Op(","));
+            rest = &rest[1..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Widest slice or sized number an expression may hold, so that a typo such as `a[99999999:0]`
+/// is reported instead of exhausting memory.
+static MAX_WIDTH: u64 = 65536;
+
+/// Parses `42`, `0x2a`, `0b101010`, reals such as `1.5` or Verilog-style sized numbers such as
+/// `8'h2a`, which may contain X and Z digits.
+fn parse_number(text: &str) -> Result<ExprValue, String> {
+    let invalid = || format!("invalid number '{}'", text);
+    let text = text.replace('_', "");
+    let (size, radix, digits) = if let Some((size, rest)) = text.split_once('\'') {
+        let size: Option<usize> = match size {
+            "" => None,
+            _ => Some(
+                size.parse()
+                    .ok()
+                    .filter(|size| *size as u64 <= MAX_WIDTH)
+                    .ok_or_else(invalid)?,
+            ),
+        };
+        let mut chars = rest.chars();
+        let radix = match chars.next().map(|c| c.to_ascii_lowercase()) {
+            Some('b') => 2,
+            Some('o') => 8,
+            Some('d') => 10,
+            Some('h') => 16,
+            _ => return Err(invalid()),
+        };
+        (size, radix, chars.as_str().to_string())
+    } else if let Some(digits) = text.strip_prefix("0x") {
+        (None, 16, digits.to_string())
+    } else if let Some(digits) = text.strip_prefix("0b") {
+        (None, 2, digits.to_string())
+    } else {
+        (None, 10, text.clone())
+    };
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+    if radix == 10 && size.is_none() && digits.contains('.') {
+        return digits.parse().map(ExprValue::Real).map_err(|_| invalid());
+    }
+
+    let bits = if radix == 10 {
+        let n: u128 = digits.parse().map_err(|_| invalid())?;
+        let width = (128 - n.leading_zeros() as usize).max(1);
+        Bits::from_u128(n, width)
+    } else {
+        let digit_width = match radix {
+            2 => 1,
+            8 => 3,
+            _ => 4,
+        };
+        let mut bits = vec![];
+        for c in digits.chars() {
+            match c.to_ascii_lowercase() {
+                'x' => bits.extend(vec![Value::X; digit_width]),
+                'z' | '?' => bits.extend(vec![Value::Z; digit_width]),
+                c => {
+                    let d = c.to_digit(radix).ok_or_else(invalid)?;
+                    bits.extend(Bits::from_u128(d as u128, digit_width).0);
+                }
+            }
+        }
+        let bits = Bits(bits);
+        let significant = bits
+            .0
+            .iter()
+            .position(|v| *v != Value::V0)
+            .unwrap_or(bits.width() - 1);
+        Bits(bits.0[significant..].to_vec())
+    };
+    Ok(ExprValue::Bits(match size {
+        Some(size) => bits.resize(size),
+        None => bits,
+    }))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+/// Binary operators from the lowest to the highest precedence.
+static PRECEDENCE: [&[(&str, BinaryOp)]; 10] = [
+    &[("||", BinaryOp::LogicalOr)],
+    &[("&&", BinaryOp::LogicalAnd)],
+    &[("|", BinaryOp::Or)],
+    &[("^", BinaryOp::Xor)],
+    &[("&", BinaryOp::And)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[
+        ("<", BinaryOp::Lt),
+        ("<=", BinaryOp::Le),
+        (">", BinaryOp::Gt),
+        (">=", BinaryOp::Ge),
+    ],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Mod),
+    ],
+];
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", op))
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = PRECEDENCE[level]
+            .iter()
+            .find(|(text, _)| self.peek_op() == Some(text))
+        {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let op = match self.peek_op() {
+            Some("~") => UnaryOp::Not,
+            Some("!") => UnaryOp::LogicalNot,
+            Some("-") => UnaryOp::Negate,
+            _ => return self.postfix(),
+        };
+        self.pos += 1;
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        while self.peek_op() == Some("[") {
+            self.pos += 1;
+            let msb = self.index()?;
+            let lsb = if self.peek_op() == Some(":") {
+                self.pos += 1;
+                self.index()?
+            } else {
+                msb
+            };
+            self.expect("]")?;
+            if msb.abs_diff(lsb) >= MAX_WIDTH {
+                return Err(format!("slice wider than {} bits", MAX_WIDTH));
+            }
+            expr = Expr::Slice(Box::new(expr), msb, lsb);
+        }
+        Ok(expr)
+    }
+
+    fn index(&mut self) -> Result<i64, String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Number(ExprValue::Bits(bits))) => {
+                self.pos += 1;
+                bits.to_u128()
+                    .and_then(|n| i64::try_from(n).ok())
+                    .ok_or_else(|| "invalid bit index".to_string())
+            }
+            _ => Err("expected a bit index".to_string()),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Ident(name)) => Ok(Expr::Signal(name)),
+            Some(Token::Number(value)) => Ok(Expr::Const(value)),
+            Some(Token::Str(s)) => Ok(Expr::Const(ExprValue::Str(s))),
+            Some(Token::Op("(")) => {
+                let expr = self.binary(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Op("{")) => {
+                let mut parts = vec![self.binary(0)?];
+                while self.peek_op() == Some(",") {
+                    self.pos += 1;
+                    parts.push(self.binary(0)?);
+                }
+                self.expect("}")?;
+                Ok(Expr::Concat(parts))
+            }
+            Some(Token::Op(op)) => Err(format!("unexpected '{}'", op)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+    };
+    let expr = parser.binary(0)?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(expr),
+        Some(_) => Err("unexpected text after expression".to_string()),
+    }
+}
+
+impl Expr {
+    /// Names of the signals the expression reads, without duplicates.
+    pub fn signals(&self) -> Vec<String> {
+        let mut names = vec![];
+        self.collect_signals(&mut names);
+        names
+    }
+
+    fn collect_signals(&self, names: &mut Vec<String>) {
+        match self {
+            Expr::Signal(name) => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            Expr::Const(_) => (),
+            Expr::Unary(_, e) | Expr::Slice(e, _, _) => e.collect_signals(names),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.collect_signals(names);
+                rhs.collect_signals(names);
+            }
+            Expr::Concat(parts) => {
+                for part in parts {
+                    part.collect_signals(names);
+                }
+            }
+        }
+    }
+
+    fn eval(&self, env: &HashMap<&str, (ExprValue, &WaveData)>) -> ExprValue {
+        match self {
+            Expr::Signal(name) => env[name.as_str()].0.clone(),
+            Expr::Const(value) => value.clone(),
+            Expr::Unary(op, e) => eval_unary(*op, &e.eval(env)),
+            Expr::Binary(op, lhs, rhs) => eval_binary(*op, &lhs.eval(env), &rhs.eval(env)),
+            Expr::Slice(e, msb, lsb) => {
+                let bits = e.eval(env).bits();
+                let width = bits.width();
+                let position = |index: i64| -> Option<usize> {
+                    match e.as_ref() {
+                        Expr::Signal(name) => env[name.as_str()].1.bit_position(index),
+                        _ => usize::try_from(index)
+                            .ok()
+                            .filter(|i| *i < width)
+                            .map(|i| width - 1 - i),
+                    }
+                };
+                let indices: Vec<i64> = if msb >= lsb {
+                    (*lsb..=*msb).rev().collect()
+                } else {
+                    (*msb..=*lsb).collect()
+                };
+                ExprValue::Bits(Bits(
+                    indices
+                        .into_iter()
+                        .map(|index| {
+                            position(index)
+                                .and_then(|pos| bits.0.get(pos).copied())
+                                .unwrap_or(Value::X)
+                        })
+                        .collect(),
+                ))
+            }
+            Expr::Concat(parts) => ExprValue::Bits(Bits(
+                parts
+                    .iter()
+                    .flat_map(|part| part.eval(env).bits().0)
+                    .collect(),
+            )),
+        }
+    }
+}
+
+fn eval_unary(op: UnaryOp, value: &ExprValue) -> ExprValue {
+    match (op, value) {
+        (UnaryOp::Negate, ExprValue::Real(r)) => ExprValue::Real(-r),
+        (UnaryOp::LogicalNot, _) => ExprValue::Bits(Bits::from_bool(value.truth().map(|b| !b))),
+        _ => ExprValue::Bits(unary_bits(op, &value.bits())),
+    }
+}
+
+fn unary_bits(op: UnaryOp, bits: &Bits) -> Bits {
+    match op {
+        UnaryOp::Not => Bits(
+            bits.0
+                .iter()
+                .map(|v| match v {
+                    Value::V0 => Value::V1,
+                    Value::V1 => Value::V0,
+                    _ => Value::X,
+                })
+                .collect(),
+        ),
+        UnaryOp::LogicalNot => Bits::from_bool(bits.truth().map(|b| !b)),
+        UnaryOp::Negate => match bits.to_u128() {
+            Some(n) => Bits::from_u128(n.wrapping_neg(), bits.width()),
+            None => Bits::unknown(bits.width()),
+        },
+    }
+}
+
+fn eval_binary(op: BinaryOp, lhs: &ExprValue, rhs: &ExprValue) -> ExprValue {
+    match (lhs, rhs) {
+        (ExprValue::Str(a), ExprValue::Str(b)) if matches!(op, BinaryOp::Eq | BinaryOp::Ne) => {
+            return ExprValue::Bits(Bits::from_bool(Some((a == b) == (op == BinaryOp::Eq))));
+        }
+        (ExprValue::Real(_), _) | (_, ExprValue::Real(_)) => {
+            if let Some(value) = real_binary(op, lhs.real(), rhs.real()) {
+                return value;
+            }
+        }
+        _ => (),
+    }
+    ExprValue::Bits(binary_bits(op, &lhs.bits(), &rhs.bits()))
+}
+
+/// Applies an arithmetic, comparison or logical operator to reals, which are `None` if unknown.
+/// Returns `None` for operators that only apply to bits.
+fn real_binary(op: BinaryOp, a: Option<f64>, b: Option<f64>) -> Option<ExprValue> {
+    let arithmetic =
+        |f: fn(f64, f64) -> f64| ExprValue::Real(a.zip(b).map_or(f64::NAN, |(a, b)| f(a, b)));
+    let compare =
+        |f: fn(f64, f64) -> bool| ExprValue::Bits(Bits::from_bool(a.zip(b).map(|(a, b)| f(a, b))));
+    let truth = |v: Option<f64>| Bits::from_bool(v.map(|v| v != 0.0));
+    Some(match op {
+        BinaryOp::Add => arithmetic(|a, b| a + b),
+        BinaryOp::Sub => arithmetic(|a, b| a - b),
+        BinaryOp::Mul => arithmetic(|a, b| a * b),
+        BinaryOp::Div => arithmetic(|a, b| a / b),
+        BinaryOp::Mod => arithmetic(|a, b| a % b),
+        BinaryOp::Eq => compare(|a, b| a == b),
+        BinaryOp::Ne => compare(|a, b| a != b),
+        BinaryOp::Lt => compare(|a, b| a < b),
+        BinaryOp::Le => compare(|a, b| a <= b),
+        BinaryOp::Gt => compare(|a, b| a > b),
+        BinaryOp::Ge => compare(|a, b| a >= b),
+        BinaryOp::LogicalAnd | BinaryOp::LogicalOr => {
+            ExprValue::Bits(binary_bits(op, &truth(a), &truth(b)))
+        }
+        _ => return None,
+    })
+}
+
+fn binary_bits(op: BinaryOp, lhs: &Bits, rhs: &Bits) -> Bits {
+    let width = lhs.width().max(rhs.width());
+    let (l, r) = (lhs.resize(width), rhs.resize(width));
+    let bitwise = |f: fn(Value, Value) -> Value| {
+        Bits(l.0.iter().zip(r.0.iter()).map(|(a, b)| f(*a, *b)).collect())
+    };
+    let arithmetic = |f: fn(u128, u128) -> Option<u128>| match (l.to_u128(), r.to_u128()) {
+        (Some(a), Some(b)) => f(a, b).map_or(Bits::unknown(width), |n| Bits::from_u128(n, width)),
+        _ => Bits::unknown(width),
+    };
+    let compare = |f: fn(u128, u128) -> bool| match (l.to_u128(), r.to_u128()) {
+        (Some(a), Some(b)) => Bits::from_bool(Some(f(a, b))),
+        _ => Bits::from_bool(None),
+    };
+    match op {
+        BinaryOp::And => bitwise(|a, b| match (a, b) {
+            (Value::V0, _) | (_, Value::V0) => Value::V0,
+            (Value::V1, Value::V1) => Value::V1,
+            _ => Value::X,
+        }),
+        BinaryOp::Or => bitwise(|a, b| match (a, b) {
+            (Value::V1, _) | (_, Value::V1) => Value::V1,
+            (Value::V0, Value::V0) => Value::V0,
+            _ => Value::X,
+        }),
+        BinaryOp::Xor => bitwise(|a, b| match (a, b) {
+            (Value::V0, Value::V0) | (Value::V1, Value::V1) => Value::V0,
+            (Value::V0, Value::V1) | (Value::V1, Value::V0) => Value::V1,
+            _ => Value::X,
+        }),
+        BinaryOp::Add => arithmetic(|a, b| Some(a.wrapping_add(b))),
+        BinaryOp::Sub => arithmetic(|a, b| Some(a.wrapping_sub(b))),
+        BinaryOp::Mul => arithmetic(|a, b| Some(a.wrapping_mul(b))),
+        BinaryOp::Div => arithmetic(u128::checked_div),
+        BinaryOp::Mod => arithmetic(u128::checked_rem),
+        BinaryOp::Eq => compare(|a, b| a == b),
+        BinaryOp::Ne => compare(|a, b| a != b),
+        BinaryOp::Lt => compare(|a, b| a < b),
+        BinaryOp::Le => compare(|a, b| a <= b),
+        BinaryOp::Gt => compare(|a, b| a > b),
+        BinaryOp::Ge => compare(|a, b| a >= b),
+        BinaryOp::LogicalAnd => Bits::from_bool(match (lhs.truth(), rhs.truth()) {
+            (Some(false), _) | (_, Some(false)) => Some(false),
+            (Some(true), Some(true)) => Some(true),
+            _ => None,
+        }),
+        BinaryOp::LogicalOr => Bits::from_bool(match (lhs.truth(), rhs.truth()) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None,
+        }),
+        BinaryOp::Shl | BinaryOp::Shr => match rhs.to_u128() {
+            Some(n) => {
+                let n = n.min(lhs.width() as u128) as usize;
+                let len = lhs.width();
+                let mut bits = vec![Value::V0; len];
+                if op == BinaryOp::Shl {
+                    bits[..len - n].copy_from_slice(&lhs.0[n..]);
+                } else {
+                    bits[n..].copy_from_slice(&lhs.0[..len - n]);
+                }
+                Bits(bits)
+            }
+            None => Bits::unknown(lhs.width()),
+        },
+    }
+}
+
+/// The value of a wave's change point, with bits extended to `width`. Events have no value.
+fn signal_value(value: &WaveValue, width: usize) -> ExprValue {
+    match value {
+        WaveValue::Scalar(v) => ExprValue::Bits(Bits(vec![*v]).resize(width)),
+        WaveValue::Vector(v) => ExprValue::Bits(Bits(
+            (0..width).map(|pos| vector_bit(v, width, pos)).collect(),
+        )),
+        WaveValue::Real(r) => ExprValue::Real(*r),
+        WaveValue::String(s) => ExprValue::Str(s.clone()),
+        WaveValue::Event => ExprValue::Bits(Bits::unknown(1)),
+    }
+}
+
+/// Computes the change points of `expr` from the waves of the signals it reads, as returned by
+/// `find`. Returns the width of the result along with the change points.
+pub fn evaluate<'a>(
+    expr: &Expr,
+    find: impl Fn(&str) -> Option<&'a WaveData>,
+) -> Result<(usize, Vec<WaveChangePoint>), String> {
+    let names = expr.signals();
+    let mut env: HashMap<&str, (ExprValue, &WaveData)> = HashMap::new();
+    for name in names.iter() {
+        let wdata = find(name).ok_or_else(|| format!("unknown signal '{}'", name))?;
+        let unknown = Bits::unknown(wdata.size as usize);
+        env.insert(name, (ExprValue::Bits(unknown), wdata));
+    }
+
+    let mut times: Vec<u64> = env
+        .values()
+        .flat_map(|(_, wdata)| wdata.data.iter().map(|cp| cp.time))
+        .chain([0])
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+
+    let mut next: HashMap<&str, usize> = names.iter().map(|name| (name.as_str(), 0)).collect();
+    let mut data: Vec<WaveChangePoint> = vec![];
+    let mut width = expr.eval(&env).width();
+    for time in times {
+        for (name, (value, wdata)) in env.iter_mut() {
+            let next = next.get_mut(name).unwrap();
+            while let Some(cp) = wdata.data.get(*next).filter(|cp| cp.time <= time) {
+                *value = signal_value(&cp.value, wdata.size as usize);
+                *next += 1;
+            }
+        }
+        let value = expr.eval(&env);
+        width = value.width();
+        let value = value.to_wave_value();
+        if data.last().is_none_or(|last| last.value != value) {
+            data.push(WaveChangePoint { time, value });
+        }
+    }
+    Ok((width, data))
+}
+
+/// Times at which `expr` becomes true or false, with whether it is true from then on. The value
+/// is true when it has a 1 bit or is a nonzero real, and false before the first change.
+pub fn truth_changes<'a>(
+    expr: &Expr,
+    find: impl Fn(&str) -> Option<&'a WaveData>,
+) -> Result<Vec<(u64, bool)>, String> {
+    let (width, data) = evaluate(expr, find)?;
+    let mut changes = vec![];
+    let mut was_true = false;
+    for cp in data {
+        let is_true = signal_value(&cp.value, width).truth() == Some(true);
+        if is_true != was_true {
+            changes.push((cp.time, is_true));
+        }
+        was_true = is_true;
+    }
+    Ok(changes)
+}
+
+/// Times at which the value of `expr` becomes true.
+pub fn find_true<'a>(
+    expr: &Expr,
+    find: impl Fn(&str) -> Option<&'a WaveData>,
+) -> Result<Vec<u64>, String> {
+    Ok(truth_changes(expr, find)?
+        .into_iter()
+        .filter(|(_, is_true)| *is_true)
+        .map(|(time, _)| time)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evaluates an expression without signals.
+    fn eval(text: &str) -> ExprValue {
+        parse(text).unwrap().eval(&HashMap::new())
+    }
+
+    /// The bits of a constant expression, such as `01xz`.
+    fn bits(text: &str) -> String {
+        eval(text)
+            .bits()
+            .0
+            .iter()
+            .map(|v| match v {
+                Value::V0 => '0',
+                Value::V1 => '1',
+                Value::X => 'x',
+                Value::Z => 'z',
+            })
+            .collect()
+    }
+
+    fn number(text: &str) -> u128 {
+        eval(text).bits().to_u128().unwrap()
+    }
+
+    fn wave(name: &str, size: u32, changes: &[(u64, WaveValue)]) -> WaveData {
+        let data = changes
+            .iter()
+            .map(|(time, value)| WaveChangePoint {
+                time: *time,
+                value: value.clone(),
+            })
+            .collect();
+        let mut wdata = WaveData::new(name.to_string(), vec![name.to_string()], data);
+        wdata.size = size;
+        wdata
+    }
+
+    fn vector(text: &str) -> WaveValue {
+        WaveValue::Vector(
+            text.chars()
+                .map(|c| if c == '1' { Value::V1 } else { Value::V0 })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |text: &str| parse(text).unwrap_err();
+        assert_eq!(error(""), "unexpected end of expression");
+        assert_eq!(error("a +"), "unexpected end of expression");
+        assert_eq!(error("(a"), "expected ')'");
+        assert_eq!(error("{a, b"), "expected '}'");
+        assert_eq!(error("a[3:0"), "expected ']'");
+        assert_eq!(error("a[b]"), "expected a bit index");
+        assert_eq!(error("a b"), "unexpected text after expression");
+        assert_eq!(error("a + )"), "unexpected ')'");
+        assert_eq!(error("a # b"), "unexpected character '#'");
+        assert_eq!(error("state == \"IDLE"), "unterminated string");
+        assert_eq!(error("8'q1"), "invalid number '8'q1'");
+        assert_eq!(error("4'b102"), "invalid number '4'b102'");
+        assert_eq!(error("65537'd0"), "invalid number '65537'd0'");
+        assert_eq!(
+            error("x[9223372036854775807:0]"),
+            "slice wider than 65536 bits"
+        );
+        assert!(parse("x[65535:0]").is_ok());
+    }
+
+    #[test]
+    fn precedence() {
+        let signal = |name: &str| Box::new(Expr::Signal(name.to_string()));
+        assert_eq!(
+            parse("a + b * c").unwrap(),
+            Expr::Binary(
+                BinaryOp::Add,
+                signal("a"),
+                Box::new(Expr::Binary(BinaryOp::Mul, signal("b"), signal("c"))),
+            )
+        );
+        assert_eq!(
+            parse("a - b - c").unwrap(),
+            Expr::Binary(
+                BinaryOp::Sub,
+                Box::new(Expr::Binary(BinaryOp::Sub, signal("a"), signal("b"))),
+                signal("c"),
+            )
+        );
+        assert_eq!(number("8'd1 + 8'd2 * 8'd3"), 7);
+        assert_eq!(number("(8'd1 + 8'd2) * 8'd3"), 9);
+        assert_eq!(number("8'd1 << 2 + 1"), 8);
+        assert_eq!(number("7 - 6 / 3 % 2"), 7);
+        assert_eq!(number("1 | 2 == 2"), 1);
+        assert_eq!(number("6 & 3 ^ 1"), 3);
+        assert_eq!(number("0 && 1 || 1"), 1);
+        assert_eq!(number("!0 + 2'd1"), 2);
+        assert_eq!(number("~4'b0101 & 4'b0011"), 0b0010);
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(number("7 / 2"), 3);
+        assert_eq!(number("7 % 2"), 1);
+        assert_eq!(bits("4'd7 / 4'd0"), "xxxx");
+        assert_eq!(bits("4'd7 % 4'd0"), "xxxx");
+        assert_eq!(eval("7.0 / 2"), ExprValue::Real(3.5));
+        assert_eq!(eval("7.5 % 2"), ExprValue::Real(1.5));
+    }
+
+    #[test]
+    fn unknown_bits() {
+        // Bitwise operators only give X where the known bits do not decide the result.
+        assert_eq!(bits("4'b1x0z & 4'b0111"), "0x0x");
+        assert_eq!(bits("4'b1x0z | 4'b1000"), "1x0x");
+        assert_eq!(bits("4'b1x0z ^ 4'b0000"), "1x0x");
+        assert_eq!(bits("~4'b01xz"), "10xx");
+        // Arithmetic and comparisons give X if any bit is unknown.
+        assert_eq!(bits("4'b1z01 + 1"), "xxxx");
+        assert_eq!(bits("-4'b000x"), "xxxx");
+        assert_eq!(bits("4'b10x0 == 4'b1000"), "x");
+        assert_eq!(bits("4'b10x0 < 4'b1100"), "x");
+        assert_eq!(bits("1 << 1'bx"), "x");
+        // Logical operators only need the operand that decides them.
+        assert_eq!(bits("1'bz && 0"), "0");
+        assert_eq!(bits("1'bx || 2'b10"), "1");
+        assert_eq!(bits("1'bx && 1"), "x");
+        assert_eq!(bits("!2'bx0"), "x");
+    }
+
+    #[test]
+    fn slices_and_concatenation() {
+        assert_eq!(bits("8'b1011_0010[7:4]"), "1011");
+        assert_eq!(bits("8'b1011_0010[1]"), "1");
+        assert_eq!(bits("8'b1011_0010[0:3]"), "0100");
+        assert_eq!(bits("4'b1010[5:3]"), "xx1");
+        assert_eq!(bits("{4'b1010, 2'b01}"), "101001");
+        assert_eq!(bits("{1'b1, 2'bxz}[2:1]"), "1x");
+
+        // Signal slices use the declared bit numbers.
+        let mut addr = wave("addr", 8, &[(0, vector("10110010"))]);
+        addr.index = Some(vcd::ReferenceIndex::Range(11, 4));
+        let flag = wave("flag", 1, &[(0, WaveValue::Scalar(Value::V1))]);
+        let find = |name: &str| match name {
+            "addr" => Some(&addr),
+            "flag" => Some(&flag),
+            _ => None,
+        };
+        let (width, data) = evaluate(&parse("{addr[11:8], flag, addr[4]}").unwrap(), find).unwrap();
+        assert_eq!(width, 6);
+        assert_eq!(data[0].value, vector("101110"));
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(bits("42"), "101010");
+        assert_eq!(bits("0"), "0");
+        assert_eq!(number("0x2a"), 42);
+        assert_eq!(number("0b101010"), 42);
+        assert_eq!(number("1_000"), 1000);
+        assert_eq!(bits("8'h2a"), "00101010");
+        assert_eq!(bits("8'o52"), "00101010");
+        assert_eq!(bits("3'd9"), "001");
+        assert_eq!(bits("'d10"), "1010");
+        assert_eq!(bits("4'hx"), "xxxx");
+        assert_eq!(bits("6'b1zz"), "0001zz");
+        assert_eq!(eval("1.5"), ExprValue::Real(1.5));
+    }
+
+    #[test]
+    fn signals() {
+        let a = wave(
+            "a",
+            1,
+            &[
+                (0, WaveValue::Scalar(Value::V0)),
+                (10, WaveValue::Scalar(Value::V1)),
+                (20, WaveValue::Scalar(Value::V0)),
+            ],
+        );
+        let b = wave(
+            "b",
+            1,
+            &[
+                (0, WaveValue::Scalar(Value::V1)),
+                (15, WaveValue::Scalar(Value::X)),
+            ],
+        );
+        let find = |name: &str| match name {
+            "a" => Some(&a),
+            "b" => Some(&b),
+            _ => None,
+        };
+        let expr = parse("a & b").unwrap();
+        assert_eq!(expr.signals(), ["a", "b"]);
+        assert_eq!(
+            truth_changes(&expr, find).unwrap(),
+            [(10, true), (15, false)]
+        );
+        assert_eq!(find_true(&parse("a | b").unwrap(), find).unwrap(), [0]);
+        assert!(evaluate(&parse("a & c").unwrap(), find).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! Undo and redo of edits.
+
+use std::collections::VecDeque;
+
+/// Most edits that can be undone.
+static LIMIT: usize = 100;
+
+/// States replaced by edits, so that the edits can be undone and redone.
+#[derive(Debug)]
+pub struct History<T> {
+    undo: VecDeque<T>,
+    redo: Vec<T>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: vec![],
+        }
+    }
+}
+
+impl<T> History<T> {
+    /// Records `state` as it was before an edit. Edits that were undone can no longer be redone
+    /// afterwards.
+    pub fn record(&mut self, state: T) {
+        if self.undo.len() == LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(state);
+        self.redo.clear();
+    }
+
+    /// Returns the state before the last edit, keeping the one given by `current` for redoing
+    /// it.
+    pub fn undo(&mut self, current: impl FnOnce() -> T) -> Option<T> {
+        let state = self.undo.pop_back()?;
+        self.redo.push(current());
+        Some(state)
+    }
+
+    /// Returns the state after the last undone edit, keeping the one given by `current` for
+    /// undoing it again.
+    pub fn redo(&mut self, current: impl FnOnce() -> T) -> Option<T> {
+        let state = self.redo.pop()?;
+        self.undo.push_back(current());
+        Some(state)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::default();
+        assert!(!history.can_undo());
+        history.record(1);
+        history.record(2);
+        assert_eq!(history.undo(|| 3), Some(2));
+        assert_eq!(history.undo(|| 2), Some(1));
+        assert_eq!(history.undo(|| 1), None);
+        assert!(history.can_redo());
+        assert_eq!(history.redo(|| 1), Some(2));
+        assert_eq!(history.redo(|| 2), Some(3));
+        assert_eq!(history.redo(|| 3), None);
+        assert_eq!(history.undo(|| 3), Some(2));
+    }
+
+    #[test]
+    fn edit_clears_redo() {
+        let mut history = History::default();
+        history.record(1);
+        assert_eq!(history.undo(|| 2), Some(1));
+        history.record(1);
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(|| 3), None);
+    }
+
+    #[test]
+    fn limit() {
+        let mut history = History::default();
+        for state in 0..LIMIT + 5 {
+            history.record(state);
+        }
+        let mut undone = vec![];
+        while let Some(state) = history.undo(|| 0) {
+            undone.push(state);
+        }
+        assert_eq!(undone.len(), LIMIT);
+        assert_eq!(undone.last(), Some(&5));
+    }
+}
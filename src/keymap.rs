@@ -0,0 +1,180 @@
+//! Keyboard shortcuts of the actions.
+//!
+//! A keybinding file changes the shortcuts of some actions and leaves the others as they are.
+//! Each line holds an action and its shortcuts separated by spaces, such as
+//! `rows.zoom-in = <Control>plus <Control>equal`. An action with nothing after `=` has no
+//! shortcut. Lines starting with `#` are comments.
+
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+use gtk::prelude::*;
+
+use crate::util::invalid_data;
+
+/// Actions that can have shortcuts, with the shortcuts they have by default.
+static DEFAULT_BINDINGS: &[(&str, &[&str])] = &[
+    ("win.open", &["<Control>o"]),
+    ("win.preferences", &["<Control>comma"]),
+    ("app.quit", &["<Control>q"]),
+    ("rows.open-session", &["<Control><Shift>o"]),
+    ("rows.save-session", &["<Control>s"]),
+    ("rows.export-image", &["<Control>e"]),
+    ("rows.undo", &["<Control>z"]),
+    ("rows.redo", &["<Control><Shift>z", "<Control>y"]),
+    ("rows.delete", &["Delete", "BackSpace"]),
+    ("rows.duplicate", &["<Control>d"]),
+    ("rows.move-up", &["<Alt>Up"]),
+    ("rows.move-down", &["<Alt>Down"]),
+    ("rows.insert-blank", &[]),
+    ("rows.insert-comment", &[]),
+    ("rows.group", &["<Control>g"]),
+    ("rows.ungroup", &["<Control><Shift>g"]),
+    ("rows.add-expression", &[]),
+    (
+        "rows.zoom-in",
+        &["<Control>plus", "<Control>equal", "<Control>KP_Add"],
+    ),
+    ("rows.zoom-out", &["<Control>minus", "<Control>KP_Subtract"]),
+    ("rows.zoom-fit", &["<Control>0"]),
+    ("rows.page-up", &["Page_Up"]),
+    ("rows.page-down", &["Page_Down"]),
+    ("rows.scroll-top", &["<Control>Home"]),
+    ("rows.scroll-bottom", &["<Control>End"]),
+    ("rows.go-to-row", &["<Control>l"]),
+    ("rows.go-start", &["Home"]),
+    ("rows.go-end", &["End"]),
+    ("rows.previous-change", &["<Control>Left"]),
+    ("rows.next-change", &["<Control>Right"]),
+    ("rows.find", &["<Control>f"]),
+    ("rows.find-next", &["F3"]),
+    ("rows.find-previous", &["<Shift>F3"]),
+    ("rows.add-marker", &["m"]),
+    ("rows.previous-marker", &["bracketleft"]),
+    ("rows.next-marker", &["bracketright"]),
+];
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(String, Vec<String>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: DEFAULT_BINDINGS
+                .iter()
+                .map(|(action, accels)| {
+                    let accels = accels.iter().map(|accel| accel.to_string()).collect();
+                    (action.to_string(), accels)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Reads a keybinding file.
+    pub fn load(filename: &Path) -> Result<Self, Error> {
+        Keymap::parse(&fs::read_to_string(filename)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut keymap = Keymap::default();
+        for (lineno, line) in text.lines().enumerate() {
+            let lineno = lineno + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((action, accels)) = line.split_once('=') else {
+                return Err(invalid_data(lineno, "expected 'action = shortcuts'"));
+            };
+            let Some((_, old)) = keymap.bindings.iter_mut().find(|(a, _)| a == action.trim())
+            else {
+                return Err(invalid_data(lineno, "unknown action"));
+            };
+            let accels: Vec<String> = accels.split_whitespace().map(str::to_string).collect();
+            if let Some(accel) = accels
+                .iter()
+                .find(|accel| gtk::ShortcutTrigger::parse_string(accel).is_none())
+            {
+                return Err(invalid_data(
+                    lineno,
+                    &format!("invalid shortcut '{}'", accel),
+                ));
+            }
+            *old = accels;
+        }
+        Ok(keymap)
+    }
+
+    pub fn accels(&self, action: &str) -> &[String] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| a == action)
+            .map_or(&[], |(_, accels)| accels)
+    }
+
+    /// Gives the application and window actions their shortcuts, which work anywhere in the
+    /// window.
+    pub fn apply_to_application(&self, application: &gtk::Application) {
+        for (action, accels) in &self.bindings {
+            if action.starts_with("app.") || action.starts_with("win.") {
+                let accels: Vec<&str> = accels.iter().map(String::as_str).collect();
+                application.set_accels_for_action(action, &accels);
+            }
+        }
+    }
+
+    /// A controller with the shortcuts of the other actions. These only work while the focus is
+    /// inside the widget it is added to, and only after the focused widget has had the chance to
+    /// handle the key, so that plain keys such as `m` can still be typed into entries.
+    pub fn shortcut_controller(&self) -> gtk::ShortcutController {
+        let controller = gtk::ShortcutController::new();
+        for (action, accels) in &self.bindings {
+            if accels.is_empty() || action.starts_with("app.") || action.starts_with("win.") {
+                continue;
+            }
+            let trigger = gtk::ShortcutTrigger::parse_string(&accels.join("|"));
+            controller.add_shortcut(gtk::Shortcut::new(
+                trigger,
+                Some(gtk::NamedAction::new(action)),
+            ));
+        }
+        controller
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shortcuts are only checked by GTK, which cannot run here, so these leave them empty.
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let keymap = Keymap::parse("# rows.undo =\n\n  # app.quit =\n").unwrap();
+        assert_eq!(keymap.accels("rows.undo"), ["<Control>z"]);
+        assert_eq!(keymap.accels("app.quit"), ["<Control>q"]);
+    }
+
+    #[test]
+    fn empty_list_clears_shortcuts() {
+        let keymap = Keymap::parse("rows.delete =\n  rows.redo  =  \n").unwrap();
+        assert!(keymap.accels("rows.delete").is_empty());
+        assert!(keymap.accels("rows.redo").is_empty());
+        assert_eq!(keymap.accels("rows.undo"), ["<Control>z"]);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let error = |text: &str| Keymap::parse(text).unwrap_err().to_string();
+        assert_eq!(error("rows.undo =\nrows.fly = F"), "line 2: unknown action");
+        assert_eq!(
+            error("# comment\nrows.undo"),
+            "line 2: expected 'action = shortcuts'"
+        );
+    }
+}
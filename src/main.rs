@@ -1,14 +1,44 @@
+mod config;
+mod expr;
+mod history;
+mod keymap;
+mod markers;
+mod menus;
+mod preferences;
+mod rows;
+mod session;
 mod signal_finder;
+mod theme;
 mod util;
 mod wave_viewer;
+use crate::config::Config;
+use crate::keymap::Keymap;
 use crate::signal_finder::SignalFinder;
+use crate::theme::Theme;
 use crate::wave_viewer::WaveViewer;
+use gtk::gio;
+use gtk::glib;
 use gtk::prelude::*;
+use std::cell::RefCell;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use vcd::*;
 
+/// The main window and what its handlers share.
+struct App {
+    window: gtk::ApplicationWindow,
+    keymap: Keymap,
+    config: RefCell<Config>,
+    /// Whether the config file may be written. It is not when it could not be read in full, so
+    /// that what could not be read is not lost.
+    config_writable: bool,
+    /// The pane holding the signal finder and the viewer of the open dump.
+    dump: RefCell<Option<(gtk::Paned, Rc<WaveViewer>)>>,
+    recent_menu: gio::Menu,
+}
+
 fn main() {
     let application =
         gtk::Application::new(Some("com.github.matsud224.wavespy"), Default::default());
@@ -17,28 +47,256 @@ fn main() {
 }
 
 fn build_ui(application: &gtk::Application) {
+    let mut errors = vec![];
+    let keymap = load_keymap(&mut errors);
+    keymap.apply_to_application(application);
+    let (config, config_writable) = load_config(&mut errors);
+
     let window = gtk::ApplicationWindow::new(application);
 
     window.set_title(Some("WaveSpy"));
-    window.set_default_size(1200, 600);
-    window.present();
+    let (width, height) = config.window_size;
+    window.set_default_size(width, height);
 
-    let mut reader = Parser::new(BufReader::new(
-        File::open("alu.vcd").expect("open file failed"),
-    ));
-    let header = reader.parse_header().expect("parse header failed");
-    let wave_viewer = Rc::new(WaveViewer::new());
-    let signal_finder = SignalFinder::new(header.items, wave_viewer.clone());
+    let recent_menu = gio::Menu::new();
+    let header_bar = gtk::HeaderBar::new();
+    header_bar.pack_start(&gtk::PopoverMenuBar::from_model(Some(&menus::menu_bar(
+        &keymap,
+        &recent_menu,
+    ))));
+    window.set_titlebar(Some(&header_bar));
 
-    let root_pane = gtk::Paned::builder()
-        .orientation(gtk::Orientation::Horizontal)
-        .start_child(&signal_finder.pane)
-        .end_child(&wave_viewer.pane)
-        .wide_handle(true)
-        .position(250)
-        .build();
+    let app = Rc::new(App {
+        window: window.clone(),
+        keymap,
+        config: RefCell::new(config),
+        config_writable,
+        dump: RefCell::new(None),
+        recent_menu,
+    });
+    app.update_recent_menu();
 
-    window.set_child(Some(&root_pane));
+    let quit = gio::SimpleAction::new("quit", None);
+    quit.connect_activate(glib::clone!(@weak application => move |_, _| application.quit()));
+    application.add_action(&quit);
+    let open = gio::SimpleAction::new("open", None);
+    open.connect_activate(glib::clone!(@strong app => move |_, _| {
+        let opener = app.clone();
+        wave_viewer::choose_file(
+            &app.window,
+            "Open Dump",
+            gtk::FileChooserAction::Open,
+            move |path| opener.open_dump(&path),
+        );
+    }));
+    window.add_action(&open);
+    let open_recent = gio::SimpleAction::new("open-recent", Some(glib::VariantTy::STRING));
+    open_recent.connect_activate(glib::clone!(@strong app => move |_, parameter| {
+        if let Some(path) = parameter.and_then(|p| p.get::<String>()) {
+            app.open_dump(Path::new(&path));
+        }
+    }));
+    window.add_action(&open_recent);
+    let preferences = gio::SimpleAction::new("preferences", None);
+    preferences.connect_activate(glib::clone!(@strong app => move |_, _| {
+        let editor = app.clone();
+        preferences::show(&app.window, &app.config.borrow(), move |config| {
+            editor.set_preferences(config)
+        });
+    }));
+    window.add_action(&preferences);
+    // Preferences changed from the menus go through the config, so that they are saved and
+    // kept for the next dump.
+    for (name, _) in Theme::NAMES {
+        let action = gio::SimpleAction::new(&format!("theme-{}", name), None);
+        action.connect_activate(glib::clone!(@strong app => move |_, _| {
+            app.change_preferences(|config| config.theme = name.to_string());
+        }));
+        window.add_action(&action);
+    }
+    for (name, density) in config::DENSITIES {
+        let action = gio::SimpleAction::new(&format!("density-{}", name), None);
+        action.connect_activate(glib::clone!(@strong app => move |_, _| {
+            app.change_preferences(|config| config.density = *density);
+        }));
+        window.add_action(&action);
+    }
+    let set_font = gio::SimpleAction::new("set-font", None);
+    set_font.connect_activate(glib::clone!(@strong app => move |_, _| {
+        let Some(pane) = app.dump.borrow().as_ref().map(|(_, viewer)| viewer.pane.clone()) else {
+            return;
+        };
+        let editor = app.clone();
+        let font = app.config.borrow().font.clone();
+        wave_viewer::prompt_text(&pane, &font, move |text| {
+            editor.change_preferences(|config| config.font = text.trim().to_string());
+        });
+    }));
+    window.add_action(&set_font);
+
+    window.connect_close_request(glib::clone!(@strong app => move |_| {
+        app.remember_layout();
+        app.save_config();
+        glib::Propagation::Proceed
+    }));
+
+    window.present();
+    if !errors.is_empty() {
+        wave_viewer::show_error(&window, &errors.join("\n"));
+    }
+
+    let config = app.config.borrow();
+    let startup = match config.recent_files.first() {
+        Some(path) if config.reopen_last => path.clone(),
+        _ => PathBuf::from("alu.vcd"),
+    };
+    drop(config);
+    app.open_dump(&startup);
 
     window.show();
 }
+
+/// Reads the keybinding file in the configuration directory, if there is one. What goes wrong is
+/// added to `errors`.
+fn load_keymap(errors: &mut Vec<String>) -> Keymap {
+    let filename = glib::user_config_dir().join("wavespy").join("keys.conf");
+    if !filename.exists() {
+        return Keymap::default();
+    }
+    Keymap::load(&filename).unwrap_or_else(|e| {
+        errors.push(format!("Failed to load {}: {}", filename.display(), e));
+        Keymap::default()
+    })
+}
+
+/// Reads the config file, if there is one, and tells whether it may be written back. What goes
+/// wrong is added to `errors`.
+fn load_config(errors: &mut Vec<String>) -> (Config, bool) {
+    let filename = Config::filename();
+    if !filename.exists() {
+        return (Config::default(), true);
+    }
+    let (config, load_errors) = match Config::load(&filename) {
+        Ok(loaded) => loaded,
+        Err(e) => (Config::default(), vec![e]),
+    };
+    for e in &load_errors {
+        errors.push(format!("Failed to load {}: {}", filename.display(), e));
+    }
+    if !load_errors.is_empty() {
+        errors.push("Preferences will not be saved until the file is fixed.".to_string());
+    }
+    (config, load_errors.is_empty())
+}
+
+/// Gives `viewer` the settings in `config` that the preferences dialog changes.
+fn apply_preferences(viewer: &WaveViewer, config: &Config) {
+    viewer.set_theme(Theme::by_name(&config.theme).unwrap_or_default());
+    viewer.set_font(&config.font);
+    viewer.set_density(config.density);
+    viewer.set_default_radix(config.radix);
+    viewer.set_time_unit(config.time_unit);
+}
+
+impl App {
+    /// Replaces the contents of the window with a signal finder and a wave viewer for the dump
+    /// in `filename`.
+    fn open_dump(&self, filename: &Path) {
+        let header = File::open(filename).and_then(|file| {
+            let mut reader = Parser::new(BufReader::new(file));
+            reader.parse_header()
+        });
+        let header = match header {
+            Ok(header) => header,
+            Err(e) => {
+                let message = format!("Failed to open {}: {}", filename.display(), e);
+                wave_viewer::show_error(&self.window, &message);
+                return;
+            }
+        };
+        // Panes of the dump that is being closed keep their sizes for the next one.
+        self.remember_layout();
+
+        let name = filename.to_string_lossy();
+        let wave_viewer = WaveViewer::new(&name);
+        let signal_finder = SignalFinder::new(header.items, wave_viewer.clone());
+
+        // The menu bar is outside the viewer, so the window needs the viewer's actions too.
+        self.window
+            .insert_action_group("rows", Some(&wave_viewer.actions));
+        wave_viewer
+            .pane
+            .add_controller(self.keymap.shortcut_controller());
+
+        let config = self.config.borrow();
+        apply_preferences(&wave_viewer, &config);
+        wave_viewer.set_column_widths(config.column_widths);
+        let root_pane = gtk::Paned::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .start_child(&signal_finder.pane)
+            .end_child(&wave_viewer.pane)
+            .wide_handle(true)
+            .position(config.finder_width)
+            .build();
+        drop(config);
+
+        self.window.set_child(Some(&root_pane));
+        self.window.set_title(Some(&format!("WaveSpy – {}", name)));
+        *self.dump.borrow_mut() = Some((root_pane, wave_viewer));
+
+        let path = filename
+            .canonicalize()
+            .unwrap_or_else(|_| filename.to_path_buf());
+        self.config.borrow_mut().add_recent(&path);
+        self.update_recent_menu();
+        self.save_config();
+    }
+
+    fn set_preferences(&self, config: Config) {
+        if let Some((_, viewer)) = &*self.dump.borrow() {
+            apply_preferences(viewer, &config);
+        }
+        *self.config.borrow_mut() = config;
+        self.update_recent_menu();
+        self.save_config();
+    }
+
+    fn change_preferences(&self, change: impl FnOnce(&mut Config)) {
+        let mut config = self.config.borrow().clone();
+        change(&mut config);
+        self.set_preferences(config);
+    }
+
+    /// Records the size of the window and the positions of its panes.
+    fn remember_layout(&self) {
+        let mut config = self.config.borrow_mut();
+        config.window_size = self.window.default_size();
+        if let Some((root_pane, viewer)) = &*self.dump.borrow() {
+            config.finder_width = root_pane.position();
+            config.column_widths = viewer.column_widths();
+        }
+    }
+
+    fn save_config(&self) {
+        if !self.config_writable {
+            return;
+        }
+        if let Err(e) = self.config.borrow().save(&Config::filename()) {
+            wave_viewer::show_error(&self.window, &format!("Failed to save preferences: {}", e));
+        }
+    }
+
+    fn update_recent_menu(&self) {
+        self.recent_menu.remove_all();
+        for path in &self.config.borrow().recent_files {
+            // Underscores in menu labels mark mnemonics unless doubled.
+            let label = path.display().to_string().replace('_', "__");
+            let item = gio::MenuItem::new(Some(&label), None);
+            item.set_action_and_target_value(
+                Some("win.open-recent"),
+                Some(&path.to_string_lossy().to_variant()),
+            );
+            self.recent_menu.append_item(&item);
+        }
+    }
+}
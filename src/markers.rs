@@ -0,0 +1,121 @@
+//! Named time markers shown as vertical lines in the wave viewer.
+
+use crate::wave_viewer::SimTime;
+
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub name: String,
+    pub time: SimTime,
+}
+
+/// Markers kept in time order.
+#[derive(Debug, Default, Clone)]
+pub struct Markers {
+    pub list: Vec<Marker>,
+    /// Number used to name the next marker, so that names are not reused after a removal.
+    next_number: usize,
+}
+
+impl Markers {
+    /// Adds a marker named `M<n>` at `time` and returns its index.
+    pub fn add(&mut self, time: SimTime) -> usize {
+        self.next_number += 1;
+        let idx = self.list.partition_point(|m| m.time <= time);
+        self.list.insert(
+            idx,
+            Marker {
+                name: format!("M{}", self.next_number),
+                time,
+            },
+        );
+        idx
+    }
+
+    pub fn remove(&mut self, idx: usize) {
+        if idx < self.list.len() {
+            self.list.remove(idx);
+        }
+    }
+
+    /// Moves the marker at `idx` to `time` and returns its new index.
+    pub fn move_to(&mut self, idx: usize, time: SimTime) -> usize {
+        if idx >= self.list.len() {
+            return idx;
+        }
+        let mut marker = self.list.remove(idx);
+        marker.time = time;
+        let idx = self.list.partition_point(|m| m.time <= time);
+        self.list.insert(idx, marker);
+        idx
+    }
+
+    pub fn rename(&mut self, idx: usize, name: String) {
+        if let Some(marker) = self.list.get_mut(idx) {
+            marker.name = name;
+        }
+    }
+
+    /// The first marker after `time`.
+    pub fn next_after(&self, time: SimTime) -> Option<&Marker> {
+        self.list.iter().find(|m| m.time > time)
+    }
+
+    /// The last marker before `time`.
+    pub fn prev_before(&self, time: SimTime) -> Option<&Marker> {
+        self.list.iter().rev().find(|m| m.time < time)
+    }
+}
+
+/// Number of clock edges in `edges`, which must be sorted, that fall after `from` and no later
+/// than `to`.
+pub fn cycles_between(edges: &[SimTime], from: SimTime, to: SimTime) -> usize {
+    let (from, to) = (from.min(to), from.max(to));
+    edges.partition_point(|t| *t <= to) - edges.partition_point(|t| *t <= from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(markers: &Markers) -> Vec<SimTime> {
+        markers.list.iter().map(|m| m.time).collect()
+    }
+
+    fn names(markers: &Markers) -> Vec<&str> {
+        markers.list.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn move_to_keeps_time_order() {
+        let mut markers = Markers::default();
+        for time in [10, 20, 30] {
+            markers.add(time);
+        }
+        assert_eq!(markers.move_to(0, 25), 1);
+        assert_eq!(times(&markers), [20, 25, 30]);
+        assert_eq!(names(&markers), ["M2", "M1", "M3"]);
+        assert_eq!(markers.move_to(2, 5), 0);
+        assert_eq!(times(&markers), [5, 20, 25]);
+        assert_eq!(names(&markers), ["M3", "M2", "M1"]);
+        assert_eq!(markers.move_to(1, 21), 1);
+        assert_eq!(times(&markers), [5, 21, 25]);
+    }
+
+    #[test]
+    fn move_to_same_time_goes_last() {
+        let mut markers = Markers::default();
+        for time in [10, 20] {
+            markers.add(time);
+        }
+        assert_eq!(markers.move_to(0, 20), 1);
+        assert_eq!(names(&markers), ["M2", "M1"]);
+    }
+
+    #[test]
+    fn move_to_missing_marker() {
+        let mut markers = Markers::default();
+        markers.add(10);
+        assert_eq!(markers.move_to(3, 20), 3);
+        assert_eq!(times(&markers), [10]);
+    }
+}
@@ -0,0 +1,129 @@
+//! The menu bar of the main window.
+
+use gtk::gio;
+use gtk::prelude::*;
+
+use crate::keymap::Keymap;
+
+/// Builds the menus, with `recent_menu` as the submenu of recently opened dumps.
+pub fn menu_bar(keymap: &Keymap, recent_menu: &gio::Menu) -> gio::Menu {
+    let section = |items: &[(&str, &str)]| {
+        let menu = gio::Menu::new();
+        for (label, action) in items {
+            let item = gio::MenuItem::new(Some(label), Some(action));
+            // Popover menus show this next to the label.
+            if let Some(accel) = keymap.accels(action).first() {
+                item.set_attribute_value("accel", Some(&accel.to_variant()));
+            }
+            menu.append_item(&item);
+        }
+        menu
+    };
+    let menu = |sections: &[gio::Menu]| {
+        let menu = gio::Menu::new();
+        for section in sections {
+            menu.append_section(None, section);
+        }
+        menu
+    };
+
+    let open_section = section(&[("Open Dump…", "win.open")]);
+    open_section.append_submenu(Some("Recent Files"), recent_menu);
+    let file_menu = menu(&[
+        open_section,
+        section(&[
+            ("Open Session…", "rows.open-session"),
+            ("Save Session…", "rows.save-session"),
+        ]),
+        section(&[("Export Image…", "rows.export-image")]),
+        section(&[("Quit", "app.quit")]),
+    ]);
+    let edit_menu = menu(&[
+        section(&[("Undo", "rows.undo"), ("Redo", "rows.redo")]),
+        section(&[
+            ("Delete", "rows.delete"),
+            ("Duplicate", "rows.duplicate"),
+            ("Move Up", "rows.move-up"),
+            ("Move Down", "rows.move-down"),
+        ]),
+        section(&[
+            ("Insert Blank Row", "rows.insert-blank"),
+            ("Insert Comment…", "rows.insert-comment"),
+        ]),
+        section(&[
+            ("Group Selected…", "rows.group"),
+            ("Ungroup", "rows.ungroup"),
+        ]),
+        section(&[("Preferences…", "win.preferences")]),
+    ]);
+    let theme_menu = section(&[
+        ("Dark", "win.theme-dark"),
+        ("Light", "win.theme-light"),
+        ("High Contrast", "win.theme-high-contrast"),
+        ("Print", "win.theme-print"),
+    ]);
+    let view_menu = menu(&[
+        section(&[
+            ("Zoom In", "rows.zoom-in"),
+            ("Zoom Out", "rows.zoom-out"),
+            ("Zoom to Fit", "rows.zoom-fit"),
+        ]),
+        section(&[
+            ("Scroll Up a Page", "rows.page-up"),
+            ("Scroll Down a Page", "rows.page-down"),
+            ("Scroll to Top", "rows.scroll-top"),
+            ("Scroll to Bottom", "rows.scroll-bottom"),
+            ("Go to Row…", "rows.go-to-row"),
+        ]),
+        section(&[
+            ("Compact Rows", "win.density-compact"),
+            ("Normal Rows", "win.density-normal"),
+            ("Tall Rows", "win.density-tall"),
+        ]),
+        section(&[("Font (such as “Monospace 10”)…", "win.set-font")]),
+    ]);
+    view_menu.append_submenu(Some("Theme"), &theme_menu);
+    let search_menu = section(&[
+        ("Find…", "rows.find"),
+        ("Find Next", "rows.find-next"),
+        ("Find Previous", "rows.find-previous"),
+    ]);
+    let markers_menu = menu(&[
+        section(&[
+            ("Add Marker at Cursor", "rows.add-marker"),
+            ("Previous Marker", "rows.previous-marker"),
+            ("Next Marker", "rows.next-marker"),
+        ]),
+        section(&[
+            ("Start of Dump", "rows.go-start"),
+            ("End of Dump", "rows.go-end"),
+            ("Previous Change of Selected Rows", "rows.previous-change"),
+            ("Next Change of Selected Rows", "rows.next-change"),
+        ]),
+        section(&[("Move Selected Marker to Cursor", "rows.move-marker")]),
+        section(&[("Use Selected Row as Clock", "rows.set-clock")]),
+    ]);
+    let tools_menu = menu(&[
+        section(&[
+            ("Add Derived Signal…", "rows.add-expression"),
+            (
+                "Combine into Bus, First Row as MSB…",
+                "rows.combine-msb-first",
+            ),
+            (
+                "Combine into Bus, First Row as LSB…",
+                "rows.combine-lsb-first",
+            ),
+        ]),
+        section(&[("Load Palette…", "rows.load-palette")]),
+    ]);
+
+    let menu_bar = gio::Menu::new();
+    menu_bar.append_submenu(Some("File"), &file_menu);
+    menu_bar.append_submenu(Some("Edit"), &edit_menu);
+    menu_bar.append_submenu(Some("View"), &view_menu);
+    menu_bar.append_submenu(Some("Search"), &search_menu);
+    menu_bar.append_submenu(Some("Markers"), &markers_menu);
+    menu_bar.append_submenu(Some("Tools"), &tools_menu);
+    menu_bar
+}
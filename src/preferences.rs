@@ -0,0 +1,105 @@
+//! The preferences dialog.
+
+use gtk::glib;
+use gtk::prelude::*;
+
+use crate::config::{Config, DENSITIES, TIME_UNITS};
+use crate::rows::Radix;
+use crate::theme::Theme;
+
+/// Shows the preferences in `config` in a dialog over `parent` and calls `on_save` with the
+/// changed copy when the user saves them.
+pub fn show(parent: &impl IsA<gtk::Window>, config: &Config, on_save: impl Fn(Config) + 'static) {
+    let themes = Theme::NAMES.map(|(name, _)| name);
+    let theme = gtk::DropDown::from_strings(&themes);
+    if let Some(pos) = themes.iter().position(|name| *name == config.theme) {
+        theme.set_selected(pos as u32);
+    }
+    let font = gtk::Entry::builder()
+        .text(&config.font)
+        .placeholder_text("Such as Monospace 10")
+        .build();
+    let radix = choice(&Radix::NAMES, &config.radix);
+    let density = choice(DENSITIES, &config.density);
+    let time_unit = choice(TIME_UNITS, &config.time_unit);
+    let reopen_last = gtk::CheckButton::builder()
+        .label("Open the most recent dump at startup")
+        .active(config.reopen_last)
+        .build();
+    let recent_limit = gtk::SpinButton::with_range(0.0, 50.0, 1.0);
+    recent_limit.set_value(config.recent_limit as f64);
+
+    let grid = gtk::Grid::builder()
+        .column_spacing(12)
+        .row_spacing(6)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .build();
+    let fields: [(&str, &gtk::Widget); 6] = [
+        ("Theme", theme.upcast_ref()),
+        ("Font", font.upcast_ref()),
+        ("Radix of new rows", radix.upcast_ref()),
+        ("Row height", density.upcast_ref()),
+        ("Time unit", time_unit.upcast_ref()),
+        ("Recent files to keep", recent_limit.upcast_ref()),
+    ];
+    for (i, (label, widget)) in fields.iter().enumerate() {
+        grid.attach(
+            &gtk::Label::builder().label(*label).xalign(0.0).build(),
+            0,
+            i as i32,
+            1,
+            1,
+        );
+        grid.attach(*widget, 1, i as i32, 1, 1);
+    }
+    grid.attach(&reopen_last, 0, fields.len() as i32, 2, 1);
+
+    let cancel_button = gtk::Button::with_label("Cancel");
+    let save_button = gtk::Button::with_label("Save");
+    save_button.add_css_class("suggested-action");
+    let buttons = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(6)
+        .halign(gtk::Align::End)
+        .build();
+    buttons.append(&cancel_button);
+    buttons.append(&save_button);
+    grid.attach(&buttons, 0, fields.len() as i32 + 1, 2, 1);
+
+    let window = gtk::Window::builder()
+        .title("Preferences")
+        .transient_for(parent)
+        .modal(true)
+        .resizable(false)
+        .child(&grid)
+        .build();
+    cancel_button.connect_clicked(glib::clone!(@weak window => move |_| window.close()));
+    let config = config.clone();
+    save_button.connect_clicked(glib::clone!(@weak window => move |_| {
+        let mut config = config.clone();
+        config.theme = themes[theme.selected() as usize].to_string();
+        config.font = font.text().to_string();
+        config.radix = Radix::NAMES[radix.selected() as usize].1;
+        config.density = DENSITIES[density.selected() as usize].1;
+        config.time_unit = TIME_UNITS[time_unit.selected() as usize].1;
+        config.reopen_last = reopen_last.is_active();
+        config.recent_limit = recent_limit.value() as usize;
+        config.recent_files.truncate(config.recent_limit);
+        on_save(config);
+        window.close();
+    }));
+    window.present();
+}
+
+/// A drop-down listing the names in `table`, with the one for `value` selected.
+fn choice<T: PartialEq>(table: &[(&str, T)], value: &T) -> gtk::DropDown {
+    let names: Vec<&str> = table.iter().map(|(name, _)| *name).collect();
+    let dropdown = gtk::DropDown::from_strings(&names);
+    if let Some(pos) = table.iter().position(|(_, v)| v == value) {
+        dropdown.set_selected(pos as u32);
+    }
+    dropdown
+}
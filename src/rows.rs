@@ -0,0 +1,719 @@
+use crate::wave_viewer::{SimTime, WaveData};
+
+#[derive(Debug, Clone)]
+pub enum RowKind {
+    Wave(WaveData),
+    /// An empty separator row.
+    Blank,
+    /// A row showing free text in the name column.
+    Comment(String),
+    /// A named set of rows that can be collapsed into its header row.
+    Group {
+        name: String,
+        collapsed: bool,
+        children: Vec<Row>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Interpolation {
+    /// Each value holds until the next change.
+    #[default]
+    Step,
+    /// Straight lines join consecutive values.
+    Linear,
+}
+
+/// Settings for drawing a wave as an analog trace.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AnalogStyle {
+    pub interpolation: Interpolation,
+    /// Values at the bottom and top of the row, or `None` to fit the range of the signal.
+    pub range: Option<(f64, f64)>,
+}
+
+/// Lowest height of a row in pixels, which leaves room for the margins above and below it.
+pub static MIN_ROW_HEIGHT: u64 = 16;
+
+/// Global preset for the height of rows that have none of their own.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Density {
+    Compact,
+    #[default]
+    Normal,
+    Tall,
+}
+
+impl Density {
+    /// Height of digital rows in pixels.
+    pub fn row_height(self) -> u64 {
+        match self {
+            Density::Compact => 20,
+            Density::Normal => 30,
+            Density::Tall => 45,
+        }
+    }
+}
+
+/// Base in which the values of vector waves are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Radix {
+    Binary,
+    Octal,
+    #[default]
+    Hex,
+    Unsigned,
+    /// Two's complement.
+    Signed,
+}
+
+impl Radix {
+    /// Every radix with the name it has in config and session files.
+    pub const NAMES: [(&'static str, Radix); 5] = [
+        ("binary", Radix::Binary),
+        ("octal", Radix::Octal),
+        ("hex", Radix::Hex),
+        ("unsigned", Radix::Unsigned),
+        ("signed", Radix::Signed),
+    ];
+
+    pub fn name(self) -> &'static str {
+        Radix::NAMES
+            .iter()
+            .find(|(_, radix)| *radix == self)
+            .map_or("", |(name, _)| name)
+    }
+
+    pub fn from_name(name: &str) -> Option<Radix> {
+        Radix::NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, radix)| *radix)
+    }
+}
+
+/// Red, green and blue components from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub f64, pub f64, pub f64);
+
+impl Color {
+    /// Parses `#rrggbb`.
+    pub fn from_hex(text: &str) -> Option<Color> {
+        let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+        let component = |i: usize| {
+            let c = u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()?;
+            Some(c as f64 / 255.0)
+        };
+        Some(Color(component(0)?, component(2)?, component(4)?))
+    }
+
+    pub fn to_hex(self) -> String {
+        let component = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            component(self.0),
+            component(self.1),
+            component(self.2)
+        )
+    }
+}
+
+/// Colors a wave wherever a condition holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRule {
+    /// Expression over signals of the dump, such as `top.valid` or `top.data == 0xdeadbeef`.
+    pub condition: String,
+    pub color: Color,
+    /// Times at which the condition becomes true or false, filled in when wave data is read.
+    pub changes: Vec<(SimTime, bool)>,
+}
+
+impl ColorRule {
+    pub fn new(condition: String, color: Color) -> Self {
+        ColorRule {
+            condition,
+            color,
+            changes: vec![],
+        }
+    }
+
+    pub fn holds_at(&self, time: SimTime) -> bool {
+        let idx = self.changes.partition_point(|(t, _)| *t <= time);
+        idx > 0 && self.changes[idx - 1].1
+    }
+}
+
+/// Color and line settings for drawing a wave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveStyle {
+    /// Color of the wave, or `None` for the default.
+    pub color: Option<Color>,
+    pub line_width: f64,
+    /// Whether the high level of scalar waves and the shapes of bus values are shaded.
+    pub fill: bool,
+    /// Rules checked in order. The first that holds overrides `color`.
+    pub rules: Vec<ColorRule>,
+}
+
+impl Default for WaveStyle {
+    fn default() -> Self {
+        WaveStyle {
+            color: None,
+            line_width: 1.0,
+            fill: false,
+            rules: vec![],
+        }
+    }
+}
+
+impl WaveStyle {
+    /// Color of the wave at `time`, or `None` for the default.
+    pub fn color_at(&self, time: SimTime) -> Option<Color> {
+        self.rules
+            .iter()
+            .find(|rule| rule.holds_at(time))
+            .map(|rule| rule.color)
+            .or(self.color)
+    }
+
+    /// Splits `start..end` into runs of the same color, as (start, end, color). The rules can
+    /// change the color while the wave keeps its value.
+    pub fn color_runs(
+        &self,
+        start: SimTime,
+        end: SimTime,
+    ) -> Vec<(SimTime, SimTime, Option<Color>)> {
+        let mut times: Vec<SimTime> = self
+            .rules
+            .iter()
+            .flat_map(|rule| rule.changes.iter().map(|(time, _)| *time))
+            .filter(|time| (start + 1..end).contains(time))
+            .collect();
+        times.sort_unstable();
+        times.dedup();
+        let mut runs: Vec<(SimTime, SimTime, Option<Color>)> = vec![];
+        for (from, to) in [start]
+            .into_iter()
+            .chain(times.iter().copied())
+            .zip(times.iter().copied().chain([end]))
+        {
+            let color = self.color_at(from);
+            match runs.last_mut() {
+                Some(last) if last.2 == color => last.1 = to,
+                _ => runs.push((from, to, color)),
+            }
+        }
+        runs
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub kind: RowKind,
+    pub selected: bool,
+    /// One scalar row per bit while a vector wave is expanded, most significant bit first.
+    pub bits: Option<Vec<Row>>,
+    /// Analog display of a wave, or `None` to draw it as a digital waveform.
+    pub analog: Option<AnalogStyle>,
+    pub radix: Radix,
+    pub style: WaveStyle,
+    /// Height in pixels, or `None` to follow the global preset.
+    pub height: Option<u64>,
+}
+
+impl Row {
+    pub fn new(kind: RowKind) -> Self {
+        Row {
+            kind,
+            selected: false,
+            bits: None,
+            analog: None,
+            radix: Radix::default(),
+            style: WaveStyle::default(),
+            height: None,
+        }
+    }
+
+    /// Height of the row in pixels. Unless set, analog rows are three times as tall as others.
+    pub fn height(&self, density: Density) -> u64 {
+        match (self.height, self.analog) {
+            (Some(height), _) => height.max(MIN_ROW_HEIGHT),
+            (None, Some(_)) => 3 * density.row_height(),
+            (None, None) => density.row_height(),
+        }
+    }
+
+    pub fn group(name: String, children: Vec<Row>) -> Self {
+        Row::new(RowKind::Group {
+            name,
+            collapsed: false,
+            children,
+        })
+    }
+
+    /// Whether the row has an expand toggle.
+    pub fn is_expandable(&self) -> bool {
+        match &self.kind {
+            RowKind::Wave(wdata) => wdata.is_vector(),
+            RowKind::Group { .. } => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_expanded(&self) -> bool {
+        match &self.kind {
+            RowKind::Group { collapsed, .. } => !collapsed,
+            _ => self.bits.is_some(),
+        }
+    }
+
+    /// Rows that are currently shown below this row: the members of an expanded group or the
+    /// bits of an expanded vector.
+    fn visible_children(&self) -> Option<&Vec<Row>> {
+        match &self.kind {
+            RowKind::Group {
+                collapsed: false,
+                children,
+                ..
+            } => Some(children),
+            _ => self.bits.as_ref(),
+        }
+    }
+
+    fn visible_children_mut(&mut self) -> Option<&mut Vec<Row>> {
+        match &mut self.kind {
+            RowKind::Group {
+                collapsed: false,
+                children,
+                ..
+            } => Some(children),
+            _ => self.bits.as_mut(),
+        }
+    }
+
+    /// Members of a group. Unlike bit rows, these can be edited like top-level rows.
+    fn children_mut(&mut self) -> Option<&mut Vec<Row>> {
+        match &mut self.kind {
+            RowKind::Group { children, .. } => Some(children),
+            _ => None,
+        }
+    }
+}
+
+/// Calls `f` with the nesting depth of each row that is shown, in display order.
+pub fn for_each_visible<'a>(rows: &'a [Row], depth: usize, f: &mut impl FnMut(usize, &'a Row)) {
+    for row in rows {
+        f(depth, row);
+        if let Some(children) = row.visible_children() {
+            for_each_visible(children, depth + 1, f);
+        }
+    }
+}
+
+/// Returns the shown row at display index `idx` with its nesting depth.
+pub fn visible_row(rows: &[Row], idx: usize) -> Option<(usize, &Row)> {
+    let mut found = None;
+    let mut i = 0;
+    for_each_visible(rows, 0, &mut |depth, row| {
+        if i == idx {
+            found = Some((depth, row));
+        }
+        i += 1;
+    });
+    found
+}
+
+pub fn visible_row_mut(rows: &mut [Row], idx: usize) -> Option<&mut Row> {
+    fn find<'a>(rows: &'a mut [Row], idx: usize, i: &mut usize) -> Option<&'a mut Row> {
+        for row in rows {
+            if *i == idx {
+                return Some(row);
+            }
+            *i += 1;
+            if let Some(children) = row.visible_children_mut() {
+                if let Some(found) = find(children, idx, i) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+    find(rows, idx, &mut 0)
+}
+
+pub fn selected_visible_indices(rows: &[Row]) -> Vec<usize> {
+    let mut indices = vec![];
+    let mut i = 0;
+    for_each_visible(rows, 0, &mut |_, row| {
+        if row.selected {
+            indices.push(i);
+        }
+        i += 1;
+    });
+    indices
+}
+
+/// Sets the selection of every shown row to `is_selected(display index)`. Rows hidden inside
+/// collapsed groups are deselected.
+pub fn set_selection(rows: &mut [Row], is_selected: &impl Fn(usize) -> bool) {
+    fn visit(rows: &mut [Row], i: &mut Option<usize>, is_selected: &impl Fn(usize) -> bool) {
+        for row in rows {
+            row.selected = i.is_some_and(is_selected);
+            let shown = i.is_some() && row.is_expanded();
+            *i = i.map(|i| i + 1);
+            if let Some(bits) = row.bits.as_mut() {
+                visit(bits, i, is_selected);
+            }
+            if let Some(children) = row.children_mut() {
+                if shown {
+                    visit(children, i, is_selected);
+                } else {
+                    visit(children, &mut None, is_selected);
+                }
+            }
+        }
+    }
+    visit(rows, &mut Some(0), is_selected);
+}
+
+/// Deletes the selected rows, returning whether there were any.
+pub fn delete_selected(rows: &mut Vec<Row>) -> bool {
+    let len = rows.len();
+    rows.retain(|row| !row.selected);
+    let mut deleted = rows.len() != len;
+    for row in rows.iter_mut() {
+        if let Some(children) = row.children_mut() {
+            deleted |= delete_selected(children);
+        }
+    }
+    deleted
+}
+
+/// Moves every selected row one step up or down among its siblings. Rows blocked by the edge of
+/// their group or by another selected row that cannot move stay where they are. Returns whether
+/// any row moved.
+pub fn move_selected(rows: &mut [Row], up: bool) -> bool {
+    let len = rows.len();
+    let order: Vec<usize> = if up {
+        (1..len).collect()
+    } else {
+        (0..len.saturating_sub(1)).rev().collect()
+    };
+    let mut moved = false;
+    for i in order {
+        let j = if up { i - 1 } else { i + 1 };
+        if rows[i].selected && !rows[j].selected {
+            rows.swap(i, j);
+            moved = true;
+        }
+    }
+    for row in rows.iter_mut() {
+        if let Some(children) = row.children_mut() {
+            moved |= move_selected(children, up);
+        }
+    }
+    moved
+}
+
+/// Inserts a copy of each selected row right after the original. The copies become the new
+/// selection. Returns whether any row was copied.
+pub fn duplicate_selected(rows: &mut Vec<Row>) -> bool {
+    let mut duplicated = false;
+    let mut new_rows = Vec::with_capacity(rows.len());
+    for mut row in rows.drain(..) {
+        if row.selected {
+            duplicated = true;
+            let copy = row.clone();
+            new_rows.push(Row {
+                selected: false,
+                ..row
+            });
+            new_rows.push(copy);
+        } else {
+            if let Some(children) = row.children_mut() {
+                duplicated |= duplicate_selected(children);
+            }
+            new_rows.push(row);
+        }
+    }
+    *rows = new_rows;
+    duplicated
+}
+
+/// Inserts `new_row` after the last selected row in display order, at the same level, or at the
+/// end if nothing is selected. Bit rows only mirror their vector, so a selected bit row puts
+/// `new_row` after the vector instead.
+pub fn insert_after_selection(rows: &mut Vec<Row>, new_row: Row) {
+    /// Returns `new_row` back if nothing in `rows` is selected.
+    fn insert(rows: &mut Vec<Row>, new_row: Row) -> Option<Row> {
+        let mut new_row = new_row;
+        for i in (0..rows.len()).rev() {
+            if let Some(children) = rows[i].children_mut() {
+                new_row = insert(children, new_row)?;
+            }
+            let bit_selected = rows[i]
+                .bits
+                .as_ref()
+                .is_some_and(|bits| bits.iter().any(|bit| bit.selected));
+            if rows[i].selected || bit_selected {
+                rows.insert(i + 1, new_row);
+                return None;
+            }
+        }
+        Some(new_row)
+    }
+    if let Some(new_row) = insert(rows, new_row) {
+        rows.push(new_row);
+    }
+}
+
+/// Moves the selected rows of each sibling list into a new group placed where the first of them
+/// was. The new groups are selected instead of their members. Returns whether any group was made.
+pub fn group_selected(rows: &mut Vec<Row>, name: &str) -> bool {
+    let mut grouped = false;
+    if let Some(first) = rows.iter().position(|row| row.selected) {
+        grouped = true;
+        let mut members = vec![];
+        let mut rest = vec![];
+        for row in rows.drain(..) {
+            if row.selected {
+                members.push(Row {
+                    selected: false,
+                    ..row
+                });
+            } else {
+                rest.push(row);
+            }
+        }
+        let mut group = Row::group(name.to_string(), members);
+        group.selected = true;
+        rest.insert(first, group);
+        *rows = rest;
+    }
+    for row in rows.iter_mut() {
+        if !row.selected {
+            if let Some(children) = row.children_mut() {
+                grouped |= group_selected(children, name);
+            }
+        }
+    }
+    grouped
+}
+
+/// Replaces each selected group with its members. Returns whether there were any.
+pub fn ungroup_selected(rows: &mut Vec<Row>) -> bool {
+    let mut ungrouped = false;
+    let mut new_rows = Vec::with_capacity(rows.len());
+    for mut row in rows.drain(..) {
+        if let Some(children) = row.children_mut() {
+            ungrouped |= ungroup_selected(children);
+        }
+        match row.kind {
+            RowKind::Group { children, .. } if row.selected => {
+                ungrouped = true;
+                new_rows.extend(children);
+            }
+            _ => new_rows.push(row),
+        }
+    }
+    *rows = new_rows;
+    ungrouped
+}
+
+/// Collapses or expands a group, or shows or hides the bit rows of a vector wave. Rows hidden by
+/// collapsing are deselected so that edits never apply to rows the user cannot see.
+pub fn toggle_expanded(row: &mut Row) {
+    match &mut row.kind {
+        RowKind::Group {
+            collapsed,
+            children,
+            ..
+        } => {
+            *collapsed = !*collapsed;
+            if *collapsed {
+                set_selection(children, &|_| false);
+            }
+        }
+        RowKind::Wave(wdata) if wdata.is_vector() => {
+            row.bits = match row.bits {
+                Some(_) => None,
+                None => Some(
+                    wdata
+                        .split_bits()
+                        .into_iter()
+                        .map(|bit| Row::new(RowKind::Wave(bit)))
+                        .collect(),
+                ),
+            };
+        }
+        _ => (),
+    }
+}
+
+/// Selected waves in display order, excluding the bit rows of expanded vectors.
+pub fn selected_waves(rows: &[Row]) -> Vec<&WaveData> {
+    let mut waves = vec![];
+    for row in rows {
+        match &row.kind {
+            RowKind::Wave(wdata) if row.selected => waves.push(wdata),
+            RowKind::Group { children, .. } => waves.extend(selected_waves(children)),
+            _ => (),
+        }
+    }
+    waves
+}
+
+pub fn for_each_wave_mut(rows: &mut [Row], f: &mut impl FnMut(&mut WaveData)) {
+    for row in rows {
+        match &mut row.kind {
+            RowKind::Wave(wdata) => f(wdata),
+            RowKind::Group { children, .. } => for_each_wave_mut(children, f),
+            _ => (),
+        }
+    }
+}
+
+/// Calls `f` for every row, including group members and the bit rows of expanded vectors.
+pub fn for_each_row_mut(rows: &mut [Row], f: &mut impl FnMut(&mut Row)) {
+    for row in rows {
+        f(row);
+        if let Some(bits) = row.bits.as_mut() {
+            for_each_row_mut(bits, f);
+        }
+        if let Some(children) = row.children_mut() {
+            for_each_row_mut(children, f);
+        }
+    }
+}
+
+/// Calls `f` for each selected row, including the bit rows of expanded vectors.
+pub fn for_each_selected_mut(rows: &mut [Row], f: &mut impl FnMut(&mut Row)) {
+    for row in rows {
+        if row.selected {
+            f(row);
+        }
+        if let Some(bits) = row.bits.as_mut() {
+            for_each_selected_mut(bits, f);
+        }
+        if let Some(children) = row.children_mut() {
+            for_each_selected_mut(children, f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(text: &str) -> Row {
+        Row::new(RowKind::Comment(text.to_string()))
+    }
+
+    /// Texts of the comment rows and names of the groups, with members in brackets.
+    fn names(rows: &[Row]) -> String {
+        let names: Vec<String> = rows
+            .iter()
+            .map(|row| match &row.kind {
+                RowKind::Comment(text) => text.clone(),
+                RowKind::Group { name, children, .. } => format!("{}[{}]", name, names(children)),
+                RowKind::Wave(wdata) => wdata.name.clone(),
+                RowKind::Blank => "-".to_string(),
+            })
+            .collect();
+        names.join(" ")
+    }
+
+    /// Selects the comment rows and groups whose text or name is in `texts`.
+    fn select(rows: &mut [Row], texts: &[&str]) {
+        for_each_row_mut(rows, &mut |row| {
+            row.selected = match &row.kind {
+                RowKind::Comment(text) | RowKind::Group { name: text, .. } => {
+                    texts.contains(&text.as_str())
+                }
+                _ => false,
+            }
+        });
+    }
+
+    #[test]
+    fn insert_after_selected_bit() {
+        let bus = WaveData {
+            size: 2,
+            ..WaveData::new("bus".to_string(), vec!["bus".to_string()], vec![])
+        };
+        let mut rows = vec![Row::new(RowKind::Wave(bus)), comment("a")];
+        toggle_expanded(&mut rows[0]);
+        rows[0].bits.as_mut().unwrap()[1].selected = true;
+        insert_after_selection(&mut rows, comment("new"));
+        assert_eq!(names(&rows), "bus new a");
+        assert_eq!(rows[0].bits.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn insert_inside_group() {
+        let mut rows = vec![
+            Row::group("g".to_string(), vec![comment("a"), comment("b")]),
+            comment("c"),
+        ];
+        select(&mut rows, &["a"]);
+        insert_after_selection(&mut rows, comment("new"));
+        assert_eq!(names(&rows), "g[a new b] c");
+        select(&mut rows, &[]);
+        insert_after_selection(&mut rows, comment("end"));
+        assert_eq!(names(&rows), "g[a new b] c end");
+    }
+
+    #[test]
+    fn move_up_and_down() {
+        let mut rows = vec![
+            comment("a"),
+            comment("b"),
+            Row::group("g".to_string(), vec![comment("c"), comment("d")]),
+        ];
+        select(&mut rows, &["a", "b"]);
+        assert!(!move_selected(&mut rows, true));
+        assert!(move_selected(&mut rows, false));
+        assert_eq!(names(&rows), "g[c d] a b");
+        select(&mut rows, &["d"]);
+        assert!(move_selected(&mut rows, true));
+        assert_eq!(names(&rows), "g[d c] a b");
+        assert!(!move_selected(&mut rows, true));
+    }
+
+    #[test]
+    fn group_and_ungroup() {
+        let mut rows = vec![
+            comment("a"),
+            comment("b"),
+            Row::group("g".to_string(), vec![comment("c"), comment("d")]),
+            comment("e"),
+        ];
+        assert!(!group_selected(&mut rows, "new"));
+        select(&mut rows, &["b", "d", "e"]);
+        assert!(group_selected(&mut rows, "new"));
+        assert_eq!(names(&rows), "a new[b e] g[c new[d]]");
+        // The new groups are selected in place of their members.
+        assert!(ungroup_selected(&mut rows));
+        assert_eq!(names(&rows), "a b e g[c d]");
+        select(&mut rows, &["a"]);
+        assert!(!ungroup_selected(&mut rows));
+    }
+
+    #[test]
+    fn color_runs_split_at_rule_changes() {
+        let red = Color(1.0, 0.0, 0.0);
+        let mut rule = ColorRule::new("valid".to_string(), red);
+        rule.changes = vec![(10, true), (20, false)];
+        let mut style = WaveStyle {
+            rules: vec![rule],
+            ..Default::default()
+        };
+        assert_eq!(
+            style.color_runs(0, 30),
+            vec![(0, 10, None), (10, 20, Some(red)), (20, 30, None)]
+        );
+        assert_eq!(style.color_runs(12, 18), vec![(12, 18, Some(red))]);
+        style.color = Some(red);
+        assert_eq!(style.color_runs(0, 30), vec![(0, 30, Some(red))]);
+    }
+}
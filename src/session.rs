@@ -0,0 +1,340 @@
+//! Saving and restoring the rows shown in the wave viewer.
+//!
+//! A session file is plain text with one row per line and fields separated by tabs, such as
+//! `wave<TAB>top.alu.a`, `blank`, `comment<TAB>text` or `group<TAB>-<TAB>inputs`, where `+`
+//! marks a collapsed group. The members of a group follow it with one more leading tab. A bus
+//! assembled from scalar signals lists its name followed by the bits, most significant first,
+//! and a derived signal its name followed by its expression. Tabs and newlines in names,
+//! comments and expressions are saved as spaces.
+//!
+//! Settings of a wave row that differ from the defaults follow an empty field as `key=value`
+//! fields, such as `wave<TAB>top.alu.a<TAB><TAB>radix=signed<TAB>height=90`. The settings are
+//! `radix=signed`, `analog=linear`, `range=0,255`, `height=90`, `color=#ff0000`, `line-width=2`,
+//! `fill=yes` and `rule=#00ff00,top.valid`, which colors the wave while `top.valid` is true.
+//!
+//! Waveform data is not stored; waves are read back from the dump when a session is loaded.
+
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+use crate::rows::{
+    AnalogStyle, Color, ColorRule, Interpolation, Radix, Row, RowKind, WaveStyle, MIN_ROW_HEIGHT,
+};
+use crate::util::invalid_data;
+use crate::wave_viewer::{WaveData, WaveSource};
+
+pub fn save(filename: &Path, rows: &[Row]) -> Result<(), Error> {
+    fs::write(filename, to_text(rows))
+}
+
+/// Reads the rows of a session. Waves are returned without data.
+pub fn load(filename: &Path) -> Result<Vec<Row>, Error> {
+    parse(&fs::read_to_string(filename)?)
+}
+
+fn to_text(rows: &[Row]) -> String {
+    let mut text = String::new();
+    write_rows(&mut text, rows, 0);
+    text
+}
+
+fn parse(text: &str) -> Result<Vec<Row>, Error> {
+    let lines: Vec<(usize, usize, Vec<&str>)> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(lineno, line)| {
+            let content = line.trim_start_matches('\t');
+            let depth = line.len() - content.len();
+            (lineno + 1, depth, content.split('\t').collect())
+        })
+        .collect();
+
+    let mut pos = 0;
+    let rows = read_rows(&lines, &mut pos, 0)?;
+    if let Some((lineno, _, _)) = lines.get(pos) {
+        return Err(invalid_data(*lineno, "unexpected indentation"));
+    }
+    Ok(rows)
+}
+
+fn write_rows(text: &mut String, rows: &[Row], depth: usize) {
+    for row in rows {
+        text.push_str(&"\t".repeat(depth));
+        match &row.kind {
+            RowKind::Wave(wdata) => {
+                match &wdata.source {
+                    WaveSource::Var => {
+                        text.push_str(&format!("wave\t{}", wdata.path.join(".")));
+                    }
+                    WaveSource::Bus(bits) => {
+                        let bits: Vec<String> = bits.iter().map(|bit| bit.join(".")).collect();
+                        text.push_str(&format!(
+                            "bus\t{}\t{}",
+                            sanitize(&wdata.name),
+                            bits.join("\t")
+                        ));
+                    }
+                    WaveSource::Expr(expr) => {
+                        text.push_str(&format!(
+                            "expr\t{}\t{}",
+                            sanitize(&wdata.name),
+                            sanitize(expr)
+                        ));
+                    }
+                }
+                let options = row_options(row);
+                if !options.is_empty() {
+                    text.push('\t');
+                }
+                for option in options {
+                    text.push('\t');
+                    text.push_str(&option);
+                }
+                text.push('\n');
+            }
+            RowKind::Blank => text.push_str("blank\n"),
+            RowKind::Comment(comment) => {
+                text.push_str(&format!("comment\t{}\n", sanitize(comment)));
+            }
+            RowKind::Group {
+                name,
+                collapsed,
+                children,
+            } => {
+                let state = if *collapsed { "+" } else { "-" };
+                text.push_str(&format!("group\t{}\t{}\n", state, sanitize(name)));
+                write_rows(text, children, depth + 1);
+            }
+        }
+    }
+}
+
+fn read_rows(
+    lines: &[(usize, usize, Vec<&str>)],
+    pos: &mut usize,
+    depth: usize,
+) -> Result<Vec<Row>, Error> {
+    let mut rows = vec![];
+    while let Some((lineno, line_depth, fields)) = lines.get(*pos) {
+        if *line_depth < depth {
+            break;
+        }
+        if *line_depth > depth {
+            return Err(invalid_data(*lineno, "unexpected indentation"));
+        }
+        *pos += 1;
+
+        let (fields, options) = match fields[..] {
+            ["wave" | "bus" | "expr", ..] => split_options(fields),
+            _ => (&fields[..], &[][..]),
+        };
+        let kind = match fields[..] {
+            ["wave", path] => {
+                let path: Vec<String> = path.split('.').map(String::from).collect();
+                RowKind::Wave(WaveData::new(path.join("."), path, vec![]))
+            }
+            ["bus", name, ref bits @ ..] if !bits.is_empty() => {
+                let bits = bits
+                    .iter()
+                    .map(|bit| bit.split('.').map(String::from).collect())
+                    .collect();
+                RowKind::Wave(WaveData::bus(name.to_string(), bits))
+            }
+            ["expr", name, expr] => {
+                RowKind::Wave(WaveData::expr(name.to_string(), expr.to_string()))
+            }
+            ["blank"] => RowKind::Blank,
+            ["comment", comment] => RowKind::Comment(comment.to_string()),
+            ["group", state @ ("+" | "-"), name] => RowKind::Group {
+                name: name.to_string(),
+                collapsed: state == "+",
+                children: read_rows(lines, pos, depth + 1)?,
+            },
+            _ => return Err(invalid_data(*lineno, "unknown row")),
+        };
+        let mut row = Row::new(kind);
+        for option in options {
+            read_option(&mut row, option)
+                .ok_or_else(|| invalid_data(*lineno, &format!("invalid setting '{}'", option)))?;
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Splits the `key=value` fields off a wave row. They follow the first empty field after the
+/// name, which paths and expressions never are, so that an expression such as `fill==1` is not
+/// taken for a setting.
+fn split_options<'a, 'b>(fields: &'a [&'b str]) -> (&'a [&'b str], &'a [&'b str]) {
+    match fields.iter().skip(2).position(|field| field.is_empty()) {
+        Some(pos) => (&fields[..pos + 2], &fields[pos + 3..]),
+        None => (fields, &[]),
+    }
+}
+
+/// Settings of a wave row that differ from the defaults, as `key=value` fields.
+fn row_options(row: &Row) -> Vec<String> {
+    let mut options = vec![];
+    if row.radix != Radix::default() {
+        options.push(format!("radix={}", row.radix.name()));
+    }
+    if let Some(analog) = &row.analog {
+        let interpolation = match analog.interpolation {
+            Interpolation::Step => "step",
+            Interpolation::Linear => "linear",
+        };
+        options.push(format!("analog={}", interpolation));
+        if let Some((min, max)) = analog.range {
+            options.push(format!("range={},{}", min, max));
+        }
+    }
+    if let Some(height) = row.height {
+        options.push(format!("height={}", height));
+    }
+    let style = &row.style;
+    if let Some(color) = style.color {
+        options.push(format!("color={}", color.to_hex()));
+    }
+    if style.line_width != WaveStyle::default().line_width {
+        options.push(format!("line-width={}", style.line_width));
+    }
+    if style.fill {
+        options.push("fill=yes".to_string());
+    }
+    for rule in &style.rules {
+        options.push(format!(
+            "rule={},{}",
+            rule.color.to_hex(),
+            sanitize(&rule.condition)
+        ));
+    }
+    options
+}
+
+/// Applies a `key=value` field to `row`, or returns `None` if it is not valid.
+fn read_option(row: &mut Row, option: &str) -> Option<()> {
+    let (key, value) = option.split_once('=')?;
+    match key {
+        "radix" => row.radix = Radix::from_name(value)?,
+        "analog" => {
+            row.analog
+                .get_or_insert_with(AnalogStyle::default)
+                .interpolation = match value {
+                "step" => Interpolation::Step,
+                "linear" => Interpolation::Linear,
+                _ => return None,
+            }
+        }
+        "range" => {
+            let (min, max) = value.split_once(',')?;
+            let range = (min.parse().ok()?, max.parse().ok()?);
+            row.analog.get_or_insert_with(AnalogStyle::default).range = Some(range);
+        }
+        "height" => {
+            row.height = Some(value.parse::<u64>().ok()?.max(MIN_ROW_HEIGHT));
+        }
+        "color" => row.style.color = Some(Color::from_hex(value)?),
+        "line-width" => row.style.line_width = value.parse().ok()?,
+        "fill" => row.style.fill = value == "yes",
+        "rule" => {
+            let (color, condition) = value.split_once(',')?;
+            let rule = ColorRule::new(condition.to_string(), Color::from_hex(color)?);
+            row.style.rules.push(rule);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Tabs and newlines would break the line structure, so they are stored as spaces.
+fn sanitize(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wave(path: &str) -> Row {
+        let path: Vec<String> = path.split('.').map(String::from).collect();
+        Row::new(RowKind::Wave(WaveData::new(path.join("."), path, vec![])))
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut styled = wave("top.data");
+        styled.radix = Radix::Signed;
+        styled.height = Some(40);
+        styled.analog = Some(AnalogStyle {
+            interpolation: Interpolation::Linear,
+            range: Some((0.0, 255.0)),
+        });
+        styled.style.color = Some(Color(1.0, 0.0, 0.0));
+        styled.style.line_width = 2.0;
+        styled.style.fill = true;
+        styled.style.rules.push(ColorRule::new(
+            "top.data == 3".to_string(),
+            Color(0.0, 1.0, 0.0),
+        ));
+        let rows = vec![
+            Row::group(
+                "inputs".to_string(),
+                vec![
+                    wave("top.clk"),
+                    Row::new(RowKind::Blank),
+                    Row::group("inner".to_string(), vec![styled]),
+                ],
+            ),
+            Row::new(RowKind::Group {
+                name: "empty".to_string(),
+                collapsed: true,
+                children: vec![],
+            }),
+            Row::new(RowKind::Comment("bus\twith a tab".to_string())),
+            Row::new(RowKind::Wave(WaveData::bus(
+                "pair".to_string(),
+                vec![
+                    vec!["top".to_string(), "a".to_string()],
+                    vec!["b".to_string()],
+                ],
+            ))),
+            Row::new(RowKind::Wave(WaveData::expr(
+                "full".to_string(),
+                "fill==1".to_string(),
+            ))),
+        ];
+        let text = to_text(&rows);
+        assert_eq!(
+            text,
+            "group\t-\tinputs\n\
+             \twave\ttop.clk\n\
+             \tblank\n\
+             \tgroup\t-\tinner\n\
+             \t\twave\ttop.data\t\tradix=signed\tanalog=linear\trange=0,255\theight=40\t\
+             color=#ff0000\tline-width=2\tfill=yes\trule=#00ff00,top.data == 3\n\
+             group\t+\tempty\n\
+             comment\tbus with a tab\n\
+             bus\tpair\ttop.a\tb\n\
+             expr\tfull\tfill==1\n"
+        );
+        let loaded = parse(&text).unwrap();
+        assert_eq!(to_text(&loaded), text);
+        match &loaded[4].kind {
+            RowKind::Wave(wdata) => {
+                assert_eq!(wdata.source, WaveSource::Expr("fill==1".to_string()));
+            }
+            _ => panic!("expected a derived signal"),
+        }
+        assert_eq!(loaded[4].style, WaveStyle::default());
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert!(parse("wave\ttop.a\n\tblank\n").is_err());
+        assert!(parse("group\t?\tname\n").is_err());
+        assert!(parse("wave\ttop.a\t\tradix=decimal\n").is_err());
+        assert!(parse("frobnicate\n").is_err());
+    }
+}
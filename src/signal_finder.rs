@@ -1,70 +1,198 @@
 use std::cell::RefCell;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
+use gtk::gio;
 use gtk::glib;
 use gtk::prelude::*;
 use vcd::*;
 
-use crate::wave_viewer;
+use crate::rows::{Row, RowKind};
+use crate::wave_viewer::{self, WaveData};
 
 pub struct SignalFinder {
     pub pane: gtk::Paned,
 }
 
+/// A scope in the hierarchy, referenced by its position rather than by a copy of its items so
+/// that nodes stay cheap to create while the tree is being expanded.
+struct ScopeNode {
+    /// Index of the scope among the items of each enclosing scope, outermost first.
+    indices: Vec<usize>,
+    scope_type: String,
+    name: String,
+}
+
+/// An entry of the variable list.
+enum VarEntry {
+    Var(Var),
+    /// Bit-blasted scalar variables `name[i]` shown as a single vector. Bits are most
+    /// significant first.
+    Bus {
+        name: String,
+        bits: Vec<String>,
+    },
+}
+
+impl VarEntry {
+    fn type_text(&self) -> String {
+        match self {
+            VarEntry::Var(var) => format!("{}", var.var_type),
+            VarEntry::Bus { .. } => "bus".to_string(),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            VarEntry::Var(var) => var_name(var),
+            VarEntry::Bus { name, .. } => name.clone(),
+        }
+    }
+
+    /// A row for the entry whose data is still to be read from the dump.
+    fn to_row(&self, hier: &[String]) -> Row {
+        let wdata = match self {
+            VarEntry::Var(var) => {
+                let path = full_path(hier, &var_name(var));
+                WaveData::new(
+                    path.clone(),
+                    path.split('.').map(String::from).collect(),
+                    vec![],
+                )
+            }
+            VarEntry::Bus { name, bits } => WaveData::bus(
+                full_path(hier, name),
+                bits.iter()
+                    .map(|bit| full_path(hier, bit).split('.').map(String::from).collect())
+                    .collect(),
+            ),
+        };
+        Row::new(RowKind::Wave(wdata))
+    }
+}
+
 impl SignalFinder {
     pub fn new(hierarchy: Vec<ScopeItem>, wview: Rc<wave_viewer::WaveViewer>) -> SignalFinder {
-        let scope_view = gtk::TreeView::builder().headers_visible(false).build();
-        let scope_store = gtk::TreeStore::new(&[glib::Type::STRING, glib::Type::STRING]);
-        create_scope_model(&scope_store, None, &hierarchy);
-        let mut scope_columns: Vec<gtk::TreeViewColumn> = Vec::new();
-        append_column("type", &mut scope_columns, &scope_view, None);
-        append_column("name", &mut scope_columns, &scope_view, None);
-        scope_view.set_model(Some(&scope_store));
-        scope_view.set_activate_on_single_click(true);
-        scope_view.expand_all();
-        scope_view.set_enable_tree_lines(true);
-
-        let var_view = gtk::TreeView::builder().headers_visible(true).build();
-        let mut var_columns: Vec<gtk::TreeViewColumn> = Vec::new();
-        append_column("type", &mut var_columns, &var_view, None);
-        append_column("name", &mut var_columns, &var_view, None);
+        let hierarchy = Rc::new(hierarchy);
+
+        let root_store = gio::ListStore::new::<glib::BoxedAnyObject>();
+        root_store.append(&glib::BoxedAnyObject::new(ScopeNode {
+            indices: vec![],
+            scope_type: "ROOT".to_string(),
+            name: "".to_string(),
+        }));
+        let scope_tree = gtk::TreeListModel::new(
+            root_store,
+            false,
+            false,
+            glib::clone!(@strong hierarchy => move |obj| {
+                let node = obj.downcast_ref::<glib::BoxedAnyObject>()?;
+                let node = node.borrow::<ScopeNode>();
+                create_scope_model(get_items(&hierarchy, &node.indices)?, &node.indices)
+            }),
+        );
+        if let Some(root_row) = scope_tree.row(0) {
+            root_row.set_expanded(true);
+        }
+
+        let scope_selection = gtk::SingleSelection::new(Some(scope_tree));
+        let scope_view = gtk::ColumnView::builder()
+            .model(&scope_selection)
+            .single_click_activate(true)
+            .build();
+        append_column("type", &scope_view, true, |row| {
+            row.borrow::<ScopeNode>().scope_type.clone()
+        });
+        append_column("name", &scope_view, false, |row| {
+            row.borrow::<ScopeNode>().name.clone()
+        });
+
+        let var_store = gio::ListStore::new::<glib::BoxedAnyObject>();
+        let var_sorted = gtk::SortListModel::new(Some(var_store.clone()), None::<gtk::Sorter>);
+        let var_selection = gtk::SingleSelection::new(Some(var_sorted.clone()));
+        let var_view = gtk::ColumnView::builder().model(&var_selection).build();
+        // Clicking a column header sets the sorter of the view, which orders the list.
+        var_sorted.set_sorter(var_view.sorter().as_ref());
+        append_var_column("type", &var_view, VarEntry::type_text);
+        append_var_column("name", &var_view, VarEntry::name);
+
+        let add_scope_button = gtk::Button::with_label("Add Scope as Group");
+        let var_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        var_box.append(
+            &gtk::ScrolledWindow::builder()
+                .child(&var_view)
+                .vexpand(true)
+                .build(),
+        );
+        var_box.append(&add_scope_button);
 
         let pane = gtk::Paned::builder()
             .orientation(gtk::Orientation::Vertical)
             .start_child(&gtk::ScrolledWindow::builder().child(&scope_view).build())
-            .end_child(&gtk::ScrolledWindow::builder().child(&var_view).build())
+            .end_child(&var_box)
             .build();
 
         let selected_hier: Rc<RefCell<Option<Vec<String>>>> = Rc::new(RefCell::new(None));
+        let selected_indices: Rc<RefCell<Vec<usize>>> = Rc::new(RefCell::new(vec![]));
 
-        var_view.connect_row_activated(
-            glib::clone!(@strong selected_hier, @strong wview => move |var_view, path, _| {
-                if let Some(m) = var_view.model() {
-                    if let Some(it) = m.iter(path) {
-                        if let Some(hier) = selected_hier.borrow().as_ref() {
-                            let var_name = m.get_value(&it, 1).get::<String>().unwrap();
-                            let var_full_path = match hier[..] {
-                                [] => var_name,
-                                _ => format!("{}.{}", hier.join("."), var_name)
-                            };
-                            wview.add_wave_by_name(&var_full_path);
+        add_scope_button.connect_clicked(
+            glib::clone!(@strong hierarchy, @strong selected_hier, @strong selected_indices, @strong wview => move |_| {
+                if let Some(hier) = selected_hier.borrow().as_ref() {
+                    if let Some(items) = get_items(&hierarchy, &selected_indices.borrow()) {
+                        wview.add_rows(vec![scope_group(hier, items)]);
+                    }
+                }
+            }),
+        );
+
+        var_view.connect_activate(
+            glib::clone!(@strong selected_hier, @strong var_selection, @strong wview => move |_, position| {
+                let Some(entry) = var_selection.item(position).and_downcast::<glib::BoxedAnyObject>() else {
+                    return;
+                };
+                let entry = entry.borrow::<VarEntry>();
+                if let Some(hier) = selected_hier.borrow().as_ref() {
+                    match &*entry {
+                        VarEntry::Var(var) => {
+                            wview.add_wave_by_name(&full_path(hier, &var_name(var)));
+                        }
+                        VarEntry::Bus { name, bits } => {
+                            let bits: Vec<String> =
+                                bits.iter().map(|bit| full_path(hier, bit)).collect();
+                            wview.add_bus(&full_path(hier, name), &bits);
                         }
                     }
                 }
             }),
         );
 
-        scope_view.connect_row_activated(glib::clone!(@strong selected_hier => move |_, path, _| {
-            let var_store = gtk::TreeStore::new(&[glib::Type::STRING, glib::Type::STRING]);
-            let (items, hier_strs) = get_vars(&hierarchy, path.indices().as_slice());
-            create_var_model(
-                &var_store,
-                None,
-                &items,
-            );
-            selected_hier.borrow_mut().replace(hier_strs);
-            var_view.set_model(Some(&var_store));
-        }));
+        scope_view.connect_activate(
+            glib::clone!(@strong selected_hier, @strong selected_indices => move |_, position| {
+                let Some(row) = scope_selection.item(position).and_downcast::<gtk::TreeListRow>() else {
+                    return;
+                };
+                let Some(node) = row.item().and_downcast::<glib::BoxedAnyObject>() else {
+                    return;
+                };
+                let node = node.borrow::<ScopeNode>();
+                let (Some(items), Some(hier)) = (
+                    get_items(&hierarchy, &node.indices),
+                    get_hier_strs(&hierarchy, &node.indices),
+                ) else {
+                    return;
+                };
+                let entries: Vec<glib::BoxedAnyObject> = list_vars(items)
+                    .into_iter()
+                    .map(glib::BoxedAnyObject::new)
+                    .collect();
+                var_store.splice(0, var_store.n_items(), &entries);
+                selected_hier.replace(Some(hier));
+                selected_indices.replace(node.indices.clone());
+            }),
+        );
 
         SignalFinder { pane }
     }
@@ -72,99 +200,224 @@ impl SignalFinder {
 
 fn append_column(
     title: &str,
-    v: &mut Vec<gtk::TreeViewColumn>,
-    left_tree: &gtk::TreeView,
-    max_width: Option<i32>,
+    view: &gtk::ColumnView,
+    with_expander: bool,
+    text: impl Fn(&glib::BoxedAnyObject) -> String + 'static,
 ) {
-    let id = v.len() as i32;
-    let renderer = gtk::CellRendererText::new();
+    let factory = gtk::SignalListItemFactory::new();
+    factory.connect_setup(move |_, obj| {
+        let item = obj.downcast_ref::<gtk::ListItem>().unwrap();
+        let label = gtk::Label::builder().xalign(0.0).build();
+        if with_expander {
+            let expander = gtk::TreeExpander::builder().child(&label).build();
+            item.set_child(Some(&expander));
+        } else {
+            item.set_child(Some(&label));
+        }
+    });
+    factory.connect_bind(move |_, obj| {
+        let item = obj.downcast_ref::<gtk::ListItem>().unwrap();
+        let Some(row) = item.item().and_downcast::<gtk::TreeListRow>() else {
+            return;
+        };
+        let Some(node) = row.item().and_downcast::<glib::BoxedAnyObject>() else {
+            return;
+        };
+        let label = if with_expander {
+            let expander = item.child().and_downcast::<gtk::TreeExpander>().unwrap();
+            expander.set_list_row(Some(&row));
+            expander.child().and_downcast::<gtk::Label>().unwrap()
+        } else {
+            item.child().and_downcast::<gtk::Label>().unwrap()
+        };
+        label.set_text(&text(&node));
+    });
+
+    let column = gtk::ColumnViewColumn::builder()
+        .title(title)
+        .factory(&factory)
+        .resizable(true)
+        .build();
+    view.append_column(&column);
+}
+
+/// Appends a column showing `text` of each entry, which clicking its header sorts by.
+fn append_var_column(title: &str, view: &gtk::ColumnView, text: fn(&VarEntry) -> String) {
+    let entry_text = move |obj: &glib::Object| {
+        obj.downcast_ref::<glib::BoxedAnyObject>()
+            .map_or(String::new(), |entry| text(&entry.borrow::<VarEntry>()))
+    };
+    let factory = gtk::SignalListItemFactory::new();
+    factory.connect_setup(|_, obj| {
+        let item = obj.downcast_ref::<gtk::ListItem>().unwrap();
+        item.set_child(Some(&gtk::Label::builder().xalign(0.0).build()));
+    });
+    factory.connect_bind(move |_, obj| {
+        let item = obj.downcast_ref::<gtk::ListItem>().unwrap();
+        let label = item.child().and_downcast::<gtk::Label>().unwrap();
+        if let Some(entry) = item.item() {
+            label.set_text(&entry_text(&entry));
+        }
+    });
+    let sorter = gtk::CustomSorter::new(move |a, b| entry_text(a).cmp(&entry_text(b)).into());
 
-    let column = gtk::TreeViewColumn::builder()
+    let column = gtk::ColumnViewColumn::builder()
         .title(title)
+        .factory(&factory)
+        .sorter(&sorter)
         .resizable(true)
-        .min_width(10)
-        .clickable(true)
-        .sort_column_id(id)
         .build();
+    view.append_column(&column);
+}
 
-    if let Some(max_width) = max_width {
-        column.set_max_width(max_width);
-        column.set_expand(true);
+/// A group of the variables of the scope `hier` holding `items`, with a nested group for each
+/// sub-scope that has variables.
+fn scope_group(hier: &[String], items: &[ScopeItem]) -> Row {
+    let mut children: Vec<Row> = list_vars(items)
+        .iter()
+        .map(|entry| entry.to_row(hier))
+        .collect();
+    for item in items {
+        if let ScopeItem::Scope(scope) = item {
+            let mut sub_hier = hier.to_vec();
+            sub_hier.push(scope.identifier.clone());
+            let group = scope_group(&sub_hier, &scope.items);
+            if matches!(&group.kind, RowKind::Group { children, .. } if !children.is_empty()) {
+                children.push(group);
+            }
+        }
     }
-    column.pack_start(&renderer, true);
-    column.add_attribute(&renderer, "text", id);
-    left_tree.append_column(&column);
-    v.push(column);
+    let name = hier.last().map_or("ROOT", |name| name.as_str());
+    Row::group(name.to_string(), children)
 }
 
-fn create_scope_model(
-    tree_store: &gtk::TreeStore,
-    parent: Option<&gtk::TreeIter>,
-    items: &Vec<ScopeItem>,
-) {
-    match parent {
-        None => {
-            let child = tree_store.append(parent);
-            tree_store.set(&child, &[(0, &"ROOT".to_string()), (1, &"".to_string())]);
-            create_scope_model(tree_store, Some(&child), items);
+/// Lists the variables of a scope, presenting families of bit-blasted scalars as buses.
+fn list_vars(items: &[ScopeItem]) -> Vec<VarEntry> {
+    let vars: Vec<&Var> = items
+        .iter()
+        .filter_map(|item| match item {
+            ScopeItem::Var(var) => Some(var),
+            _ => None,
+        })
+        .collect();
+
+    let mut families: HashMap<String, Vec<(i64, String)>> = HashMap::new();
+    for var in vars.iter() {
+        if let Some((base, bit)) = blasted_bit(var) {
+            families.entry(base).or_default().push((bit, var_name(var)));
         }
-        _ => {
-            for item in items {
-                if let ScopeItem::Scope(Scope {
-                    scope_type,
-                    identifier,
-                    items,
-                    ..
-                }) = item
-                {
-                    let child = tree_store.append(parent);
-                    tree_store.set(&child, &[(0, &format!("{}", scope_type)), (1, identifier)]);
-                    create_scope_model(tree_store, Some(&child), items);
+    }
+    families.retain(|_, bits| bits.len() > 1);
+
+    let mut entries = vec![];
+    let mut listed_families = HashSet::new();
+    for var in vars {
+        match blasted_bit(var) {
+            Some((base, _)) if families.contains_key(&base) => {
+                if listed_families.insert(base.clone()) {
+                    let mut bits = families[&base].clone();
+                    bits.sort_by_key(|(bit, _)| cmp::Reverse(*bit));
+                    let msb = bits.first().unwrap().0;
+                    let lsb = bits.last().unwrap().0;
+                    entries.push(VarEntry::Bus {
+                        name: format!("{}[{}:{}]", base, msb, lsb),
+                        bits: bits.into_iter().map(|(_, name)| name).collect(),
+                    });
                 }
             }
+            _ => entries.push(VarEntry::Var(var.clone())),
         }
     }
+    entries
 }
 
-fn get_vars(items: &[ScopeItem], indices: &[i32]) -> (Vec<ScopeItem>, Vec<String>) {
-    let mut current_scope = Vec::from(items);
-    let mut hier_str_vec: Vec<String> = vec![];
-    let (_, rest) = indices.split_first().unwrap();
-    for idx in rest {
+/// If `var` is a single bit of a bit-blasted vector, returns the vector's name and the bit index.
+/// Dumpers write these either as `name [i]` or with the index in the reference as `name[i]`.
+fn blasted_bit(var: &Var) -> Option<(String, i64)> {
+    if var.size != 1 {
+        return None;
+    }
+    match var.index {
+        Some(ReferenceIndex::BitSelect(bit)) => Some((var.reference.clone(), bit as i64)),
+        Some(_) => None,
+        None => {
+            let (base, bit) = var.reference.strip_suffix(']')?.split_once('[')?;
+            Some((base.to_string(), bit.parse().ok()?))
+        }
+    }
+}
+
+/// Name of a variable as used in signal paths, including a single-bit index.
+fn var_name(var: &Var) -> String {
+    match var.index {
+        Some(ReferenceIndex::BitSelect(bit)) => format!("{}[{}]", var.reference, bit),
+        _ => var.reference.clone(),
+    }
+}
+
+fn full_path(hier: &[String], name: &str) -> String {
+    match hier {
+        [] => name.to_string(),
+        _ => format!("{}.{}", hier.join("."), name),
+    }
+}
+
+/// Builds the child model of a scope, or `None` if it has no sub-scopes so that no expander is
+/// shown. Called by the tree model only when the scope is expanded.
+fn create_scope_model(items: &[ScopeItem], parent_indices: &[usize]) -> Option<gio::ListModel> {
+    let store = gio::ListStore::new::<glib::BoxedAnyObject>();
+    for (idx, item) in items.iter().enumerate() {
         if let ScopeItem::Scope(Scope {
-            items, identifier, ..
-        }) = current_scope
-            .into_iter()
-            .filter(|item| matches!(item, ScopeItem::Scope(_)))
-            .nth(*idx as usize)
-            .unwrap()
+            scope_type,
+            identifier,
+            ..
+        }) = item
         {
+            let mut indices = parent_indices.to_vec();
+            indices.push(idx);
+            store.append(&glib::BoxedAnyObject::new(ScopeNode {
+                indices,
+                scope_type: format!("{}", scope_type),
+                name: identifier.clone(),
+            }));
+        }
+    }
+
+    if store.n_items() == 0 {
+        None
+    } else {
+        Some(store.upcast())
+    }
+}
+
+/// Items of the scope at `indices`, or `None` if there is no such scope.
+fn get_items<'a>(items: &'a [ScopeItem], indices: &[usize]) -> Option<&'a [ScopeItem]> {
+    let mut current_scope = items;
+    for idx in indices {
+        if let Some(ScopeItem::Scope(Scope { items, .. })) = current_scope.get(*idx) {
             current_scope = items;
-            hier_str_vec.push(identifier);
         } else {
-            panic!("ScopeItem Changed");
+            return None;
         }
     }
-    (current_scope, hier_str_vec)
+    Some(current_scope)
 }
 
-fn create_var_model(
-    tree_store: &gtk::TreeStore,
-    parent: Option<&gtk::TreeIter>,
-    items: &Vec<ScopeItem>,
-) {
-    for item in items {
-        if let ScopeItem::Var(Var {
-            var_type,
-            size: _,
-            code: _,
-            reference,
-            index: _,
-            ..
-        }) = item
+/// Names of the scope at `indices` and of those enclosing it, outermost first, or `None` if
+/// there is no such scope.
+fn get_hier_strs(items: &[ScopeItem], indices: &[usize]) -> Option<Vec<String>> {
+    let mut current_scope = items;
+    let mut hier_str_vec: Vec<String> = vec![];
+    for idx in indices {
+        if let Some(ScopeItem::Scope(Scope {
+            items, identifier, ..
+        })) = current_scope.get(*idx)
         {
-            let child = tree_store.append(parent);
-            tree_store.set(&child, &[(0, &format!("{}", var_type)), (1, reference)]);
+            current_scope = items;
+            hier_str_vec.push(identifier.clone());
+        } else {
+            return None;
         }
     }
+    Some(hier_str_vec)
 }
@@ -0,0 +1,228 @@
+//! Colors used to draw the wave viewer.
+//!
+//! Besides the built-in themes, a palette file can override any color. Each line holds a key and
+//! a color, such as `background = #202020`. A `base = light` line picks the theme that the other
+//! lines change, which is the dark theme otherwise. Lines starting with `#` are comments.
+
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+use crate::rows::Color;
+use crate::util::invalid_data;
+
+/// Makes one of the built-in themes.
+type BuiltIn = fn() -> Theme;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    /// Background of selected rows.
+    pub selection: Color,
+    /// Lines between rows.
+    pub separator: Color,
+    /// Names and values.
+    pub text: Color,
+    pub comment: Color,
+    pub group: Color,
+    /// Waves that have no color of their own.
+    pub wave: Color,
+    pub high_impedance: Color,
+    /// Outline and label of bus values with unknown bits.
+    pub unknown: Color,
+    pub unknown_fill: Color,
+    /// Bounds of analog waves.
+    pub scale: Color,
+    pub marker: Color,
+    pub cursor: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            background: Color(0.0, 0.0, 0.0),
+            selection: Color(0.2, 0.2, 0.5),
+            separator: Color(0.0, 0.0, 0.4),
+            text: Color(1.0, 1.0, 1.0),
+            comment: Color(0.6, 0.8, 0.6),
+            group: Color(1.0, 0.8, 0.4),
+            wave: Color(1.0, 1.0, 1.0),
+            high_impedance: Color(0.9, 0.8, 0.2),
+            unknown: Color(1.0, 0.3, 0.3),
+            unknown_fill: Color(0.5, 0.0, 0.0),
+            scale: Color(0.6, 0.6, 0.6),
+            marker: Color(0.0, 0.8, 0.8),
+            cursor: Color(1.0, 1.0, 0.0),
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            background: Color(1.0, 1.0, 1.0),
+            selection: Color(0.75, 0.82, 1.0),
+            separator: Color(0.85, 0.85, 0.92),
+            text: Color(0.0, 0.0, 0.0),
+            comment: Color(0.2, 0.5, 0.2),
+            group: Color(0.6, 0.4, 0.0),
+            wave: Color(0.0, 0.0, 0.5),
+            high_impedance: Color(0.7, 0.5, 0.0),
+            unknown: Color(0.8, 0.0, 0.0),
+            unknown_fill: Color(1.0, 0.8, 0.8),
+            scale: Color(0.4, 0.4, 0.4),
+            marker: Color(0.0, 0.5, 0.6),
+            cursor: Color(0.9, 0.5, 0.0),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Theme {
+            background: Color(0.0, 0.0, 0.0),
+            selection: Color(0.0, 0.3, 0.8),
+            separator: Color(0.5, 0.5, 0.5),
+            text: Color(1.0, 1.0, 1.0),
+            comment: Color(0.0, 1.0, 0.0),
+            group: Color(1.0, 1.0, 0.0),
+            wave: Color(1.0, 1.0, 1.0),
+            high_impedance: Color(1.0, 1.0, 0.0),
+            unknown: Color(1.0, 0.0, 0.0),
+            unknown_fill: Color(0.6, 0.0, 0.0),
+            scale: Color(1.0, 1.0, 1.0),
+            marker: Color(0.0, 1.0, 1.0),
+            cursor: Color(1.0, 0.0, 1.0),
+        }
+    }
+
+    /// Black on white with shades of grey, for images that are going to be printed.
+    pub fn print() -> Self {
+        Theme {
+            background: Color(1.0, 1.0, 1.0),
+            selection: Color(0.9, 0.9, 0.9),
+            separator: Color(0.8, 0.8, 0.8),
+            text: Color(0.0, 0.0, 0.0),
+            comment: Color(0.3, 0.3, 0.3),
+            group: Color(0.0, 0.0, 0.0),
+            wave: Color(0.0, 0.0, 0.0),
+            high_impedance: Color(0.5, 0.5, 0.5),
+            unknown: Color(0.0, 0.0, 0.0),
+            unknown_fill: Color(0.8, 0.8, 0.8),
+            scale: Color(0.4, 0.4, 0.4),
+            marker: Color(0.3, 0.3, 0.3),
+            cursor: Color(0.5, 0.5, 0.5),
+        }
+    }
+
+    /// Every built-in theme with the name it has in config and palette files.
+    pub const NAMES: [(&'static str, BuiltIn); 4] = [
+        ("dark", Theme::dark),
+        ("light", Theme::light),
+        ("high-contrast", Theme::high_contrast),
+        ("print", Theme::print),
+    ];
+
+    /// The built-in theme called `name`.
+    pub fn by_name(name: &str) -> Option<Self> {
+        Theme::NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, theme)| theme())
+    }
+
+    /// Reads a palette file.
+    pub fn load(filename: &Path) -> Result<Self, Error> {
+        Theme::parse(&fs::read_to_string(filename)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let lines: Vec<(usize, &str, &str)> = text
+            .lines()
+            .enumerate()
+            .map(|(lineno, line)| (lineno + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(lineno, line)| match line.split_once('=') {
+                Some((key, value)) => Ok((lineno, key.trim(), value.trim())),
+                None => Err(invalid_data(lineno, "expected 'key = value'")),
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut theme = Theme::dark();
+        for (lineno, key, value) in &lines {
+            if *key == "base" {
+                theme =
+                    Theme::by_name(value).ok_or_else(|| invalid_data(*lineno, "unknown theme"))?;
+            }
+        }
+        for (lineno, key, value) in lines {
+            if key == "base" {
+                continue;
+            }
+            let color = Color::from_hex(value)
+                .ok_or_else(|| invalid_data(lineno, "expected a color such as #ff8000"))?;
+            *theme
+                .color_mut(key)
+                .ok_or_else(|| invalid_data(lineno, "unknown key"))? = color;
+        }
+        Ok(theme)
+    }
+
+    fn color_mut(&mut self, key: &str) -> Option<&mut Color> {
+        Some(match key {
+            "background" => &mut self.background,
+            "selection" => &mut self.selection,
+            "separator" => &mut self.separator,
+            "text" => &mut self.text,
+            "comment" => &mut self.comment,
+            "group" => &mut self.group,
+            "wave" => &mut self.wave,
+            "high-impedance" => &mut self.high_impedance,
+            "unknown" => &mut self.unknown,
+            "unknown-fill" => &mut self.unknown_fill,
+            "scale" => &mut self.scale,
+            "marker" => &mut self.marker,
+            "cursor" => &mut self.cursor,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_and_comments() {
+        let theme =
+            Theme::parse("# wave = #000000\n\nwave = #ff8000\n  cursor=#0000ff  \n").unwrap();
+        assert_eq!(theme.wave, Color::from_hex("#ff8000").unwrap());
+        assert_eq!(theme.cursor, Color::from_hex("#0000ff").unwrap());
+        assert_eq!(theme.background, Theme::dark().background);
+    }
+
+    #[test]
+    fn base_is_applied_before_colors() {
+        let theme = Theme::parse("text = #ff0000\nbase = light\n").unwrap();
+        assert_eq!(theme.text, Color::from_hex("#ff0000").unwrap());
+        assert_eq!(theme.background, Theme::light().background);
+        assert_eq!(Theme::parse("base = print").unwrap(), Theme::print());
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let error = |text: &str| Theme::parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error("wave = #ffffff\nshadow = #000000"),
+            "line 2: unknown key"
+        );
+        assert_eq!(error("base = sepia"), "line 1: unknown theme");
+        assert_eq!(
+            error("wave = orange"),
+            "line 1: expected a color such as #ff8000"
+        );
+        assert_eq!(error("wave"), "line 1: expected 'key = value'");
+    }
+}
@@ -1,3 +1,5 @@
+use std::io::{Error, ErrorKind};
+
 use vcd::*;
 
 #[allow(dead_code)]
@@ -27,3 +29,8 @@ pub fn print_items(items: &Vec<ScopeItem>, depth: usize) {
         }
     }
 }
+
+/// The error for line `lineno` of a text file that cannot be read.
+pub fn invalid_data(lineno: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {}: {}", lineno, msg))
+}
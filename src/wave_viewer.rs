@@ -1,21 +1,40 @@
+use gtk::gdk;
+use gtk::gio;
 use gtk::glib;
+use gtk::pango;
 use gtk::prelude::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::cmp;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use vcd::*;
 
-type SimTime = u64;
+use crate::expr;
+use crate::history::History;
+use crate::markers::{self, Markers};
+use crate::rows::{
+    self, AnalogStyle, Color, ColorRule, Density, Interpolation, Radix, Row, RowKind, WaveStyle,
+    MIN_ROW_HEIGHT,
+};
+use crate::session;
+use crate::theme::Theme;
+
+pub type SimTime = u64;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum WaveValue {
     Scalar(vcd::Value),
     Vector(vcd::Vector),
-    Custom(String),
+    Real(f64),
+    String(String),
+    /// A trigger of an event variable, which carries no value.
+    Event,
 }
 
 #[derive(Clone, Debug)]
@@ -30,373 +49,3337 @@ impl WaveChangePoint {
     }
 }
 
+/// Where the change points of a wave come from.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum WaveSource {
+    /// The variable in the dump named by `WaveData::path`.
+    #[default]
+    Var,
+    /// A vector assembled from scalar variables, most significant bit first.
+    Bus(Vec<Vec<String>>),
+    /// A signal computed from other signals by an expression such as `valid & ready`.
+    Expr(String),
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct WaveData {
     pub name: String,
     pub path: Vec<String>,
-    pub data: Vec<WaveChangePoint>,
+    /// Shared between copies of the row, such as those kept for undoing edits.
+    pub data: Rc<[WaveChangePoint]>,
+    pub source: WaveSource,
+    /// Declared width of the variable in bits.
+    pub size: u32,
+    /// Declared bit range of the variable, such as `[7:0]`.
+    pub index: Option<ReferenceIndex>,
+    /// Type of the variable in the dump. Waves computed from other waves have none unless they
+    /// are reals or strings.
+    pub var_type: Option<VarType>,
 }
 
 impl WaveData {
-    fn new(name: String, path: Vec<String>, data: Vec<WaveChangePoint>) -> Self {
-        WaveData { name, path, data }
+    pub fn new(name: String, path: Vec<String>, data: Vec<WaveChangePoint>) -> Self {
+        WaveData {
+            name,
+            path,
+            data: data.into(),
+            source: WaveSource::Var,
+            size: 1,
+            index: None,
+            var_type: None,
+        }
+    }
+
+    /// A bus whose data is filled in by `load_wave_data`.
+    pub fn bus(name: String, bits: Vec<Vec<String>>) -> Self {
+        WaveData {
+            size: bits.len() as u32,
+            source: WaveSource::Bus(bits),
+            ..WaveData::new(name.clone(), vec![name], vec![])
+        }
+    }
+
+    /// A derived signal whose data is filled in by `load_wave_data`.
+    pub fn expr(name: String, text: String) -> Self {
+        WaveData {
+            source: WaveSource::Expr(text),
+            ..WaveData::new(name.clone(), vec![name], vec![])
+        }
+    }
+
+    /// Evaluates the expression `text` over `waves`, which must hold every signal it reads.
+    fn from_expr(
+        name: String,
+        text: &str,
+        waves: &HashMap<Vec<String>, WaveData>,
+    ) -> Result<Self, String> {
+        let parsed = expr::parse(text)?;
+        let (width, data) = expr::evaluate(&parsed, |name| {
+            let path: Vec<String> = name.split('.').map(String::from).collect();
+            waves.get(&path)
+        })?;
+        // Derived signals have no declared type, but one that is a real or a string must not
+        // be taken for a vector.
+        let var_type = match data.first().map(|cp| &cp.value) {
+            Some(WaveValue::Real(_)) => Some(VarType::Real),
+            Some(WaveValue::String(_)) => Some(VarType::String),
+            _ => None,
+        };
+        Ok(WaveData {
+            data: data.into(),
+            size: width as u32,
+            var_type,
+            ..WaveData::expr(name, text.to_string())
+        })
+    }
+
+    /// Assembles a vector wave from scalar waves given most significant bit first. Bits are X
+    /// until their first change. A change point is made only where the combined value changes.
+    pub fn from_bits(name: String, bits: &[&WaveData]) -> Self {
+        let mut times: Vec<SimTime> = bits
+            .iter()
+            .flat_map(|bit| bit.data.iter().map(|cp| cp.time))
+            .collect();
+        times.sort_unstable();
+        times.dedup();
+
+        let mut next = vec![0; bits.len()];
+        let mut current = vec![vcd::Value::X; bits.len()];
+        let mut data: Vec<WaveChangePoint> = vec![];
+        for time in times {
+            for (pos, bit) in bits.iter().enumerate() {
+                while let Some(cp) = bit.data.get(next[pos]).filter(|cp| cp.time <= time) {
+                    if let WaveValue::Scalar(v) = cp.value {
+                        current[pos] = v;
+                    }
+                    next[pos] += 1;
+                }
+            }
+            let value = WaveValue::Vector(current.iter().copied().collect());
+            if data.last().is_none_or(|last| last.value != value) {
+                data.push(WaveChangePoint::new(time, value));
+            }
+        }
+
+        WaveData {
+            data: data.into(),
+            ..WaveData::bus(name, bits.iter().map(|bit| bit.path.clone()).collect())
+        }
+    }
+
+    fn from_var(path: Vec<String>, var: &Var) -> Self {
+        WaveData {
+            size: var.size,
+            index: var.index,
+            var_type: Some(var.var_type),
+            ..WaveData::new(path.join("."), path, vec![])
+        }
+    }
+
+    /// The value at `time`, or `None` before the first change.
+    pub fn value_at(&self, time: SimTime) -> Option<&WaveValue> {
+        let idx = self.data.partition_point(|cp| cp.time <= time);
+        idx.checked_sub(1).map(|i| &self.data[i].value)
+    }
+
+    pub fn is_event(&self) -> bool {
+        self.var_type == Some(VarType::Event)
+    }
+
+    /// Whether the wave has more than one bit. Reals and strings do not count, whatever their
+    /// size.
+    pub fn is_vector(&self) -> bool {
+        self.size > 1 && !matches!(self.var_type, Some(VarType::Real | VarType::String))
+    }
+
+    /// Index of the bit at position `pos`, counting from the most significant bit, as written in
+    /// the HDL source.
+    fn bit_index(&self, pos: usize) -> i64 {
+        match self.index {
+            Some(ReferenceIndex::Range(msb, lsb)) if msb >= lsb => msb as i64 - pos as i64,
+            Some(ReferenceIndex::Range(msb, _)) => msb as i64 + pos as i64,
+            Some(ReferenceIndex::BitSelect(bit)) => bit as i64,
+            None => self.size as i64 - 1 - pos as i64,
+        }
+    }
+
+    /// Position, counting from the most significant bit, of the bit with index `index`.
+    pub fn bit_position(&self, index: i64) -> Option<usize> {
+        let pos = match self.index {
+            Some(ReferenceIndex::Range(msb, lsb)) if msb >= lsb => msb as i64 - index,
+            Some(ReferenceIndex::Range(msb, _)) => index - msb as i64,
+            Some(ReferenceIndex::BitSelect(bit)) => bit as i64 - index,
+            None => self.size as i64 - 1 - index,
+        };
+        usize::try_from(pos)
+            .ok()
+            .filter(|pos| *pos < self.size as usize)
+    }
+
+    /// Splits a vector wave into one scalar wave per bit, most significant bit first.
+    pub fn split_bits(&self) -> Vec<WaveData> {
+        let width = self.size as usize;
+        (0..width)
+            .map(|pos| {
+                let mut data: Vec<WaveChangePoint> = vec![];
+                for cp in self.data.iter() {
+                    if let WaveValue::Vector(v) = &cp.value {
+                        let value = WaveValue::Scalar(vector_bit(v, width, pos));
+                        if data.last().is_none_or(|last| last.value != value) {
+                            data.push(WaveChangePoint::new(cp.time, value));
+                        }
+                    }
+                }
+                let name = format!("{}[{}]", self.name, self.bit_index(pos));
+                WaveData::new(name, self.path.clone(), data)
+            })
+            .collect()
+    }
+}
+
+/// Returns the bit at position `pos` (from the MSB) of `v` as a `width`-bit value. VCD drops
+/// leading bits, which are zero unless the leftmost written bit is X or Z.
+pub fn vector_bit(v: &vcd::Vector, width: usize, pos: usize) -> vcd::Value {
+    let pad = width.saturating_sub(v.len());
+    if pos < pad {
+        match v.iter().next() {
+            Some(first @ (vcd::Value::X | vcd::Value::Z)) => first,
+            _ => vcd::Value::V0,
+        }
+    } else {
+        v.iter().nth(pos - pad).unwrap_or(vcd::Value::X)
     }
 }
 
 pub struct WaveViewer {
     pub pane: gtk::Box,
+    filename: String,
+    /// Divides the name column from the value column.
+    name_pane: gtk::Paned,
+    /// Divides the name and value columns from the wave column.
+    column_pane: gtk::Paned,
     name_area: gtk::DrawingArea,
     value_area: gtk::DrawingArea,
     wave_area: gtk::DrawingArea,
-    waves: Rc<RefCell<Vec<WaveData>>>,
+    name_header: gtk::DrawingArea,
+    value_header: gtk::DrawingArea,
+    /// Times of the shown part of the dump, kept above `wave_area` while the rows scroll.
+    ruler_area: gtk::DrawingArea,
+    /// Activity of the shown waves over the whole dump, with the part in view highlighted.
+    overview_area: gtk::DrawingArea,
+    overview_counts: RefCell<Option<OverviewCounts>>,
+    /// File, timescale, dump end, number of shown waves, cursor and pointer times.
+    status_label: gtk::Label,
+    /// Time under the mouse pointer while it is over `wave_area`.
+    pointer: Cell<Option<SimTime>>,
+    /// Vertical scroll position shared by the name, value and wave columns.
+    vadjustment: gtk::Adjustment,
+    /// Position of the view in the dump, shown by the scrollbar below the waves.
+    hadjustment: gtk::Adjustment,
+    rows: Rc<RefCell<Vec<Row>>>,
+    /// First and last time shown in `wave_area`.
+    view: Rc<Cell<(SimTime, SimTime)>>,
+    cursor: Rc<Cell<Option<SimTime>>>,
+    /// Time of the last timestamp in the dump, read when the viewer is created.
+    dump_end: Rc<Cell<SimTime>>,
+    search_entry: gtk::SearchEntry,
+    match_button: gtk::MenuButton,
+    match_list: gtk::ListBox,
+    /// The last searched condition and the times at which it becomes true.
+    search_matches: RefCell<(String, Vec<SimTime>)>,
+    markers: Rc<RefCell<Markers>>,
+    /// Name and rising edge times of the signal used to count cycles between markers.
+    clock: RefCell<Option<(String, Vec<SimTime>)>>,
+    timescale: Option<(u32, TimescaleUnit)>,
+    /// Unit in which times are shown, or `None` for the unit of `timescale`.
+    time_unit: Cell<Option<TimescaleUnit>>,
+    /// Radix given to rows when they are added.
+    default_radix: Cell<Radix>,
+    marker_panel: gtk::Box,
+    marker_list: gtk::ListBox,
+    delta_grid: gtk::Grid,
+    clock_label: gtk::Label,
+    appearance: RefCell<Appearance>,
+    history: RefCell<History<Snapshot>>,
+    /// Everything that can be done to the viewer, as the `rows` action group.
+    pub actions: gio::SimpleActionGroup,
 }
 
-static ROW_HEIGHT: u64 = 30;
-static MARGIN_UP_DOWN: u64 = 5;
-static MARGIN_SIDE: u64 = 5;
-
-impl WaveViewer {
-    pub fn new() -> WaveViewer {
-        let name_area = gtk::DrawingArea::builder().build();
-        let value_area = gtk::DrawingArea::builder().build();
-        let wave_area = gtk::DrawingArea::builder().build();
-
-        let waves = Rc::new(RefCell::new(vec![]));
-
-        name_area.set_draw_func(
-            glib::clone!(@strong waves => move |area, cr, width, _height| {
-                draw_background(cr);
-
-                let mut max_w : u64 = 0;
-                let mut y = 0;
-                for wobj in waves.borrow().iter() {
-                    let (w, h) = draw_wave_name(cr, width, wobj);
-                    cr.translate(0.0, h as f64);
-                    y += h;
-                    max_w = u64::max(max_w, w);
-                }
+/// The rows and markers as they were before an edit.
+struct Snapshot {
+    rows: Vec<Row>,
+    markers: Markers,
+}
 
-                area.set_content_width(max_w as i32);
-                area.set_content_height(y as i32);
-            }),
-        );
+/// Changes of the shown waves counted per pixel of the overview, kept until the waves, the width
+/// of the overview or the end of the dump change.
+struct OverviewCounts {
+    width: i32,
+    dump_end: SimTime,
+    /// Data of the waves that were counted, which is replaced rather than changed in place.
+    waves: Vec<Rc<[WaveChangePoint]>>,
+    counts: Vec<u32>,
+}
 
-        value_area.set_draw_func(
-            glib::clone!(@strong waves => move |area, cr, width, _height| {
-                draw_background(cr);
-
-                let mut max_w : u64 = 0;
-                let mut y = 0;
-                for wobj in waves.borrow().iter() {
-                    let (w, h) = draw_wave_value(cr,width, wobj);
-                    cr.translate(0.0, h as f64);
-                    y += h;
-                    max_w = u64::max(max_w, w);
-                }
+impl OverviewCounts {
+    fn new(width: i32, dump_end: SimTime, waves: Vec<Rc<[WaveChangePoint]>>) -> Self {
+        let buckets = width.max(1) as usize;
+        let mut counts = vec![0u32; buckets];
+        for cp in waves.iter().flat_map(|data| data.iter()) {
+            let i = (cp.time as u128 * buckets as u128 / (dump_end as u128 + 1)) as usize;
+            counts[i.min(buckets - 1)] += 1;
+        }
+        OverviewCounts {
+            width,
+            dump_end,
+            waves,
+            counts,
+        }
+    }
 
-                area.set_content_width(max_w as i32);
-                area.set_content_height(y as i32);
-            }),
-        );
+    fn is_for(&self, width: i32, dump_end: SimTime, waves: &[Rc<[WaveChangePoint]>]) -> bool {
+        self.width == width
+            && self.dump_end == dump_end
+            && self.waves.len() == waves.len()
+            && self.waves.iter().zip(waves).all(|(a, b)| Rc::ptr_eq(a, b))
+    }
+}
 
-        wave_area.set_draw_func(
-            glib::clone!(@strong waves => move |area, cr, width, _height| {
-                draw_background(cr);
+/// Font and colors used to draw the three areas.
+struct Appearance {
+    /// Font of the names, values and wave labels.
+    font: pango::FontDescription,
+    theme: Theme,
+    density: Density,
+}
 
-                let mut y = 0;
-                for wobj in waves.borrow().iter() {
-                    let h = draw_wave(cr, width, wobj);
-                    cr.translate(0.0, h as f64);
-                    y += h;
-                }
+static MARGIN_UP_DOWN: u64 = 5;
+static MARGIN_SIDE: u64 = 5;
+static RULER_HEIGHT: u64 = 24;
+static OVERVIEW_HEIGHT: i32 = 32;
+/// Farthest a click can be from a change of the wave under it for the cursor to snap to the
+/// change, in pixels.
+static SNAP_DISTANCE: f64 = 5.0;
+/// Narrowest gap between the ticks of the time ruler, in pixels.
+static RULER_TICK_SPACING: f64 = 100.0;
+static INDENT_WIDTH: u64 = 16;
+pub static DEFAULT_FONT: &str = "Sans 10";
+/// Widest crossing at either end of a bus value, in pixels.
+static BUS_SLOPE: f64 = 4.0;
 
-                area.set_content_height(y as i32);
-            }),
-        );
+impl WaveViewer {
+    pub fn new(filename: &str) -> Rc<WaveViewer> {
+        let name_area = gtk::DrawingArea::builder().focusable(true).build();
+        let value_area = gtk::DrawingArea::builder().build();
+        let wave_area = gtk::DrawingArea::builder().focusable(true).build();
 
+        let rows: Rc<RefCell<Vec<Row>>> = Rc::new(RefCell::new(vec![]));
+        // The whole dump is in view at first.
+        let dump_end = read_dump_end(filename);
+        let view = Rc::new(Cell::new((0, if dump_end > 0 { dump_end } else { 50000 })));
+        let cursor: Rc<Cell<Option<SimTime>>> = Rc::new(Cell::new(None));
+        let dump_end = Rc::new(Cell::new(dump_end));
+        let markers: Rc<RefCell<Markers>> = Rc::default();
         let scroll_hbox = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
             .build();
+        let hadjustment = gtk::Adjustment::new(0.0, 0.0, 1.0, 1.0, 1.0, 1.0);
         let wave_scrollbar = gtk::Scrollbar::builder()
-            .adjustment(&gtk::Adjustment::new(
-                10.0, 0.0, 10000000.0, 1.0, 1.0, 1000.0,
-            ))
+            .adjustment(&hadjustment)
             .hexpand(true)
             .build();
-        scroll_hbox.append(&gtk::Button::from_icon_name("go-first-symbolic"));
-        scroll_hbox.append(&gtk::Button::from_icon_name("go-last-symbolic"));
-        scroll_hbox.append(&gtk::Button::from_icon_name("go-previous-symbolic"));
-        scroll_hbox.append(&gtk::Button::from_icon_name("go-next-symbolic"));
-        scroll_hbox.append(&gtk::Separator::new(gtk::Orientation::Vertical));
-        scroll_hbox.append(&gtk::Label::new(Some("0 ns")));
+        scroll_hbox.append(
+            &gtk::Button::builder()
+                .icon_name("go-first-symbolic")
+                .tooltip_text("Start of Dump")
+                .action_name("rows.go-start")
+                .build(),
+        );
+        scroll_hbox.append(
+            &gtk::Button::builder()
+                .icon_name("go-last-symbolic")
+                .tooltip_text("End of Dump")
+                .action_name("rows.go-end")
+                .build(),
+        );
+        scroll_hbox.append(
+            &gtk::Button::builder()
+                .icon_name("go-previous-symbolic")
+                .tooltip_text("Previous Change of Selected Rows")
+                .action_name("rows.previous-change")
+                .build(),
+        );
+        scroll_hbox.append(
+            &gtk::Button::builder()
+                .icon_name("go-next-symbolic")
+                .tooltip_text("Next Change of Selected Rows")
+                .action_name("rows.next-change")
+                .build(),
+        );
         scroll_hbox.append(&gtk::Separator::new(gtk::Orientation::Vertical));
         scroll_hbox.append(&wave_scrollbar);
 
-        let main_area = gtk::ScrolledWindow::builder()
-            .child(
-                &gtk::Paned::builder()
-                    .orientation(gtk::Orientation::Horizontal)
-                    .start_child(
-                        &gtk::Paned::builder()
-                            .orientation(gtk::Orientation::Horizontal)
-                            .position(100)
-                            .start_child(
-                                &gtk::ScrolledWindow::builder()
-                                    .child(&name_area)
-                                    .vscrollbar_policy(gtk::PolicyType::Never)
-                                    .hscrollbar_policy(gtk::PolicyType::Automatic)
-                                    .vexpand(true)
-                                    .hexpand(true)
-                                    .build(),
-                            )
-                            .end_child(
-                                &gtk::ScrolledWindow::builder()
-                                    .child(&value_area)
-                                    .vscrollbar_policy(gtk::PolicyType::Never)
-                                    .hscrollbar_policy(gtk::PolicyType::Automatic)
-                                    .vexpand(true)
-                                    .hexpand(true)
-                                    .build(),
-                            )
-                            .build(),
-                    )
-                    .end_child(&wave_area)
-                    .wide_handle(true)
-                    .position(200)
+        let name_header = gtk::DrawingArea::builder()
+            .content_height(RULER_HEIGHT as i32)
+            .build();
+        let value_header = gtk::DrawingArea::builder()
+            .content_height(RULER_HEIGHT as i32)
+            .build();
+        let ruler_area = gtk::DrawingArea::builder()
+            .content_height(RULER_HEIGHT as i32)
+            .build();
+        // Only the names and values scroll horizontally; all three columns scroll vertically
+        // together through `vadjustment`.
+        let column = |header: &gtk::DrawingArea, area: &gtk::DrawingArea| {
+            let column = gtk::Box::builder()
+                .orientation(gtk::Orientation::Vertical)
+                .build();
+            column.append(header);
+            column.append(
+                &gtk::ScrolledWindow::builder()
+                    .child(area)
+                    .vscrollbar_policy(gtk::PolicyType::Never)
+                    .hscrollbar_policy(gtk::PolicyType::Automatic)
+                    .vexpand(true)
+                    .hexpand(true)
                     .build(),
-            )
-            .vscrollbar_policy(gtk::PolicyType::Automatic)
-            .hscrollbar_policy(gtk::PolicyType::Never)
+            );
+            column
+        };
+        let wave_column = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        wave_column.append(&ruler_area);
+        wave_area.set_vexpand(true);
+        wave_area.set_hexpand(true);
+        wave_column.append(&wave_area);
+
+        let vadjustment = gtk::Adjustment::new(0.0, 0.0, 1.0, 1.0, 1.0, 1.0);
+        let main_area = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
             .build();
+        let name_pane = gtk::Paned::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .position(100)
+            .start_child(&column(&name_header, &name_area))
+            .end_child(&column(&value_header, &value_area))
+            .build();
+        let column_pane = gtk::Paned::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .start_child(&name_pane)
+            .end_child(&wave_column)
+            .wide_handle(true)
+            .position(200)
+            .hexpand(true)
+            .build();
+        main_area.append(&column_pane);
+        main_area.append(
+            &gtk::Scrollbar::builder()
+                .orientation(gtk::Orientation::Vertical)
+                .adjustment(&vadjustment)
+                .build(),
+        );
 
         let vbox = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .homogeneous(false)
             .build();
-        vbox.append(&main_area);
+        let marker_panel = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .width_request(200)
+            .build();
+        vbox.append(
+            &gtk::Paned::builder()
+                .orientation(gtk::Orientation::Horizontal)
+                .start_child(&main_area)
+                .end_child(&marker_panel)
+                .resize_end_child(false)
+                .vexpand(true)
+                .build(),
+        );
+        let overview_area = gtk::DrawingArea::builder()
+            .content_height(OVERVIEW_HEIGHT)
+            .build();
+        vbox.append(&overview_area);
         vbox.append(&scroll_hbox);
+        let status_label = gtk::Label::builder()
+            .xalign(0.0)
+            .ellipsize(pango::EllipsizeMode::End)
+            .margin_start(6)
+            .margin_end(6)
+            .margin_top(2)
+            .margin_bottom(2)
+            .build();
+        vbox.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
+        vbox.append(&status_label);
 
-        WaveViewer {
+        let viewer = Rc::new(WaveViewer {
             pane: vbox,
+            filename: filename.to_string(),
+            name_pane,
+            column_pane,
             name_area,
             value_area,
             wave_area,
-            waves,
-        }
+            name_header,
+            value_header,
+            ruler_area,
+            overview_area,
+            status_label,
+            pointer: Cell::new(None),
+            vadjustment,
+            hadjustment,
+            rows,
+            view,
+            cursor,
+            dump_end,
+            search_entry: gtk::SearchEntry::builder()
+                .placeholder_text("Condition, such as top.cpu.pc == 'h80000040")
+                .hexpand(true)
+                .build(),
+            match_button: gtk::MenuButton::builder().label("No matches").build(),
+            match_list: gtk::ListBox::new(),
+            search_matches: RefCell::new((String::new(), vec![])),
+            markers,
+            clock: RefCell::new(None),
+            timescale: read_timescale(filename),
+            time_unit: Cell::new(None),
+            default_radix: Cell::new(Radix::default()),
+            marker_panel,
+            marker_list: gtk::ListBox::new(),
+            delta_grid: gtk::Grid::builder()
+                .column_spacing(8)
+                .row_spacing(2)
+                .build(),
+            clock_label: gtk::Label::builder().xalign(0.0).build(),
+            appearance: RefCell::new(Appearance {
+                font: pango::FontDescription::from_string(DEFAULT_FONT),
+                theme: Theme::default(),
+                density: Density::default(),
+            }),
+            overview_counts: RefCell::new(None),
+            history: RefCell::default(),
+            actions: gio::SimpleActionGroup::new(),
+        });
+        viewer.setup_drawing();
+        viewer.setup_scrolling();
+        viewer.setup_hover();
+        viewer.setup_overview();
+        viewer.update_status();
+        viewer.setup_row_editing();
+        viewer.setup_search();
+        viewer.setup_markers();
+        viewer
     }
 
     pub fn add_wave_by_name(&self, name: &str) {
         let v: Vec<String> = name.split('.').map(String::from).collect();
-        let wdata = extract_wave_from_vcd("alu.vcd", v);
-        if let Ok(wdata) = wdata {
-            self.waves.borrow_mut().push(wdata);
-            self.redraw();
+        match extract_wave_from_vcd(&self.filename, v) {
+            Ok(wdata) => {
+                let mut row = Row::new(RowKind::Wave(wdata));
+                row.radix = self.default_radix.get();
+                self.record_edit();
+                self.rows.borrow_mut().push(row);
+                self.redraw();
+            }
+            Err(e) => show_error(&self.pane, &format!("Failed to read {}: {}", name, e)),
         }
     }
 
-    fn redraw(&self) {
-        self.name_area.queue_draw();
-        self.value_area.queue_draw();
-        self.wave_area.queue_draw();
+    /// Adds a single vector row assembled from the scalar signals `bits`, given most
+    /// significant bit first.
+    pub fn add_bus(&self, name: &str, bits: &[String]) {
+        let bits = bits
+            .iter()
+            .map(|bit| bit.split('.').map(String::from).collect())
+            .collect();
+        self.add_rows(vec![Row::new(RowKind::Wave(WaveData::bus(
+            name.to_string(),
+            bits,
+        )))]);
     }
-}
 
-fn draw_background(cr: &gtk::cairo::Context) {
-    cr.set_source_rgb(0.0, 0.0, 0.0);
-    cr.paint().unwrap();
-}
+    /// Appends rows whose waves have not been read yet, reading the dump only once.
+    pub fn add_rows(&self, mut new_rows: Vec<Row>) {
+        let radix = self.default_radix.get();
+        rows::for_each_row_mut(&mut new_rows, &mut |row| row.radix = radix);
+        if let Err(e) = self.load_wave_data(&mut new_rows) {
+            show_error(&self.pane, &format!("Failed to read the dump: {}", e));
+            return;
+        }
+        self.record_edit();
+        self.rows.borrow_mut().append(&mut new_rows);
+        self.redraw();
+        let mut count: usize = 0;
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, _| count += 1);
+        if let Some(last) = count.checked_sub(1) {
+            self.scroll_to_row(last);
+        }
+    }
 
-enum Align {
-    Right,
-    Left,
-}
+    /// Adds a row computed from the expression `text`, which also serves as its name.
+    pub fn add_expression(&self, text: &str) -> Result<(), String> {
+        expr::parse(text)?;
+        self.add_rows(vec![Row::new(RowKind::Wave(WaveData::expr(
+            text.to_string(),
+            text.to_string(),
+        )))]);
+        Ok(())
+    }
 
-fn draw_text(cr: &gtk::cairo::Context, y: u64, width: i32, align: Align, text: &str) {
-    let text_ext = cr.text_extents(text).unwrap();
-    match align {
-        Align::Left => {
-            cr.move_to(MARGIN_SIDE as f64, (y + ROW_HEIGHT - MARGIN_UP_DOWN) as f64);
+    pub fn save_session(&self, filename: &Path) -> Result<(), Error> {
+        session::save(filename, &self.rows.borrow())
+    }
+
+    /// Replaces the current rows with those of a saved session.
+    pub fn load_session(&self, filename: &Path) -> Result<(), Error> {
+        let mut new_rows = session::load(filename)?;
+        self.load_wave_data(&mut new_rows)?;
+        self.record_edit();
+        *self.rows.borrow_mut() = new_rows;
+        self.redraw();
+        Ok(())
+    }
+
+    /// Fills in the data of every wave in `rows` from the dump, keeping the names given to them.
+    /// Expressions that cannot be evaluated are reported and leave their rows empty.
+    fn load_wave_data(&self, rows: &mut [Row]) -> Result<(), Error> {
+        let mut paths = vec![];
+        rows::for_each_wave_mut(rows, &mut |wdata| match &wdata.source {
+            WaveSource::Var => paths.push(wdata.path.clone()),
+            WaveSource::Bus(bits) => paths.extend(bits.iter().cloned()),
+            WaveSource::Expr(text) => paths.extend(signal_paths(text)),
+        });
+        rows::for_each_row_mut(rows, &mut |row| {
+            for rule in &row.style.rules {
+                paths.extend(signal_paths(&rule.condition));
+            }
+        });
+        let (waves, _) = extract_waves_from_vcd(&self.filename, &paths)?;
+        let waves: HashMap<Vec<String>, WaveData> = waves
+            .into_iter()
+            .map(|wdata| (wdata.path.clone(), wdata))
+            .collect();
+        let mut errors = vec![];
+        rows::for_each_wave_mut(rows, &mut |wdata| match &wdata.source {
+            WaveSource::Var => {
+                if let Some(loaded) = waves.get(&wdata.path) {
+                    *wdata = WaveData {
+                        name: wdata.name.clone(),
+                        ..loaded.clone()
+                    };
+                }
+            }
+            WaveSource::Bus(bits) => {
+                let bit_waves: Option<Vec<&WaveData>> =
+                    bits.iter().map(|bit| waves.get(bit)).collect();
+                if let Some(bit_waves) = bit_waves {
+                    *wdata = WaveData::from_bits(wdata.name.clone(), &bit_waves);
+                }
+            }
+            WaveSource::Expr(text) => match WaveData::from_expr(wdata.name.clone(), text, &waves) {
+                Ok(computed) => *wdata = computed,
+                Err(e) => errors.push(format!("Failed to evaluate '{}': {}", text, e)),
+            },
+        });
+        rows::for_each_row_mut(rows, &mut |row| {
+            for rule in row.style.rules.iter_mut() {
+                match condition_changes(&rule.condition, &waves) {
+                    Ok(changes) => rule.changes = changes,
+                    Err(e) => {
+                        errors.push(format!("Failed to evaluate '{}': {}", rule.condition, e))
+                    }
+                }
+            }
+        });
+        if !errors.is_empty() {
+            show_error(&self.pane, &errors.join("\n"));
         }
-        Align::Right => {
-            cr.move_to(
-                width as f64 - MARGIN_SIDE as f64 - text_ext.width(),
-                (y + ROW_HEIGHT - MARGIN_UP_DOWN) as f64,
+        Ok(())
+    }
+
+    /// Colors the selected waves with `color` wherever `condition` holds.
+    pub fn add_color_rule(&self, color: Color, condition: &str) -> Result<(), String> {
+        expr::parse(condition)?;
+        let (waves, _) = extract_waves_from_vcd(&self.filename, &signal_paths(condition))
+            .map_err(|e| e.to_string())?;
+        let waves: HashMap<Vec<String>, WaveData> = waves
+            .into_iter()
+            .map(|wdata| (wdata.path.clone(), wdata))
+            .collect();
+        let rule = ColorRule {
+            changes: condition_changes(condition, &waves)?,
+            ..ColorRule::new(condition.to_string(), color)
+        };
+        self.update_selected_waves(|row| row.style.rules.push(rule.clone()));
+        Ok(())
+    }
+
+    fn setup_drawing(self: &Rc<Self>) {
+        let viewer = self.clone();
+        self.name_header
+            .set_draw_func(glib::clone!(@weak viewer => move |_, cr, width, _height| {
+                viewer.draw_header(cr, width, "Name");
+            }));
+        self.value_header.set_draw_func(
+            glib::clone!(@weak viewer => move |_, cr, width, _height| {
+                viewer.draw_header(cr, width, "Value");
+            }),
+        );
+        self.ruler_area
+            .set_draw_func(glib::clone!(@weak viewer => move |_, cr, width, _height| {
+                viewer.draw_ruler(cr, width);
+            }));
+        self.name_area.set_draw_func(
+            glib::clone!(@weak viewer => move |area, cr, width, _height| {
+                let w = viewer.draw_names(cr, width, viewer.vadjustment.value());
+                area.set_content_width(w as i32);
+            }),
+        );
+        self.value_area.set_draw_func(
+            glib::clone!(@weak viewer => move |area, cr, width, _height| {
+                let w = viewer.draw_values(cr, width, viewer.vadjustment.value());
+                area.set_content_width(w as i32);
+            }),
+        );
+        self.wave_area
+            .set_draw_func(glib::clone!(@weak viewer => move |_, cr, width, height| {
+                viewer.draw_waves(cr, width, height, viewer.vadjustment.value());
+            }));
+    }
+
+    /// Scrolls the three columns together. Rows are drawn shifted up by the value of
+    /// `vadjustment`, whose range is kept up to date by `update_scroll_range`.
+    fn setup_scrolling(self: &Rc<Self>) {
+        let viewer = self.clone();
+        self.vadjustment
+            .connect_value_changed(glib::clone!(@weak viewer => move |_| {
+                viewer.name_area.queue_draw();
+                viewer.value_area.queue_draw();
+                viewer.wave_area.queue_draw();
+            }));
+        // `update_scroll_range` keeps the adjustment on the view, so it only moves the view when
+        // the user drags the scrollbar.
+        self.hadjustment
+            .connect_value_changed(glib::clone!(@weak viewer => move |adjustment| {
+                let (start_time, end_time) = viewer.view.get();
+                let new_start = adjustment.value() as SimTime;
+                if new_start != start_time {
+                    viewer.view.set((new_start, new_start + (end_time - start_time)));
+                    viewer.redraw();
+                }
+            }));
+        self.wave_area
+            .connect_resize(glib::clone!(@weak viewer => move |_, _, _| {
+                viewer.update_scroll_range();
+            }));
+
+        for area in [&self.name_area, &self.value_area, &self.wave_area] {
+            let scroll = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+            scroll.connect_scroll(
+                glib::clone!(@weak viewer => @default-return glib::Propagation::Proceed, move |_, _, dy| {
+                    viewer.scroll_by(dy * viewer.vadjustment.step_increment());
+                    glib::Propagation::Stop
+                }),
             );
+            area.add_controller(scroll);
         }
     }
 
-    cr.show_text(text).ok();
-}
+    /// Follows the pointer over `wave_area` for the status bar and for tooltips.
+    fn setup_hover(self: &Rc<Self>) {
+        let viewer = self.clone();
+        let motion = gtk::EventControllerMotion::new();
+        motion.connect_motion(glib::clone!(@weak viewer => move |_, x, _| {
+            let (start_time, end_time) = viewer.view.get();
+            let width = viewer.wave_area.width();
+            viewer.pointer.set(Some(x_to_time(x, start_time, end_time, width)));
+            viewer.update_status();
+        }));
+        motion.connect_leave(glib::clone!(@weak viewer => move |_| {
+            viewer.pointer.set(None);
+            viewer.update_status();
+        }));
+        self.wave_area.add_controller(motion);
 
-fn draw_wave_name(cr: &gtk::cairo::Context, width: i32, wdata: &WaveData) -> (u64, u64) {
-    let text = wdata.name.clone();
-    let text_ext = cr.text_extents(&text).unwrap();
+        self.wave_area.set_has_tooltip(true);
+        self.wave_area.connect_query_tooltip(
+            glib::clone!(@weak viewer => @default-return false, move |_, x, y, _, tooltip| {
+                match viewer.describe_segment(x as f64, y as f64) {
+                    Some(text) => {
+                        tooltip.set_text(Some(&text));
+                        true
+                    }
+                    None => false,
+                }
+            }),
+        );
+    }
 
-    cr.set_source_rgb(1.0, 1.0, 1.0);
-    cr.set_line_join(gtk::cairo::LineJoin::Bevel);
+    /// Describes the value of the wave under (`x`, `y`) in `wave_area`: the name of the signal,
+    /// the value in the radix of its row, and when the value starts, ends and how long it lasts.
+    fn describe_segment(&self, x: f64, y: f64) -> Option<String> {
+        let idx = self.row_at(y)?;
+        let rows = self.rows.borrow();
+        let (_, row) = rows::visible_row(&rows, idx)?;
+        let RowKind::Wave(wdata) = &row.kind else {
+            return None;
+        };
+        let (start_time, end_time) = self.view.get();
+        let time = x_to_time(x, start_time, end_time, self.wave_area.width());
+        let next = wdata.data.partition_point(|cp| cp.time <= time);
+        let cp = wdata.data.get(next.checked_sub(1)?)?;
+        let end = wdata
+            .data
+            .get(next)
+            .map_or(cmp::max(self.dump_end.get(), cp.time), |cp| cp.time);
+        let value = if wdata.is_event() {
+            format!("trigger {} of {}", next, wdata.data.len())
+        } else {
+            format_value(&cp.value, wdata.size as usize, row.radix)
+        };
+        Some(format!(
+            "{}\n{}\n{} – {} ({})",
+            wdata.name,
+            value,
+            self.time_label(cp.time),
+            self.time_label(end),
+            self.time_label(end - cp.time)
+        ))
+    }
 
-    draw_text(cr, 0, width, Align::Left, &text);
-    cr.stroke().unwrap();
+    /// Pans the view to the part of the dump picked in `overview_area`. Dragging the highlighted
+    /// window moves it with the pointer; pressing elsewhere centres it on the pointer first.
+    fn setup_overview(self: &Rc<Self>) {
+        let viewer = self.clone();
+        self.overview_area.set_draw_func(
+            glib::clone!(@weak viewer => move |_, cr, width, height| {
+                viewer.draw_overview(cr, width, height);
+            }),
+        );
 
-    cr.set_source_rgb(0.0, 0.0, 0.4);
-    cr.move_to(0 as f64, ROW_HEIGHT as f64);
-    cr.line_to(width as f64, ROW_HEIGHT as f64);
-    cr.stroke().unwrap();
+        // Distance from the left edge of the window to the pointer while dragging.
+        let grab_offset = Rc::new(Cell::new(0.0));
+        let drag = gtk::GestureDrag::new();
+        drag.connect_drag_begin(
+            glib::clone!(@weak viewer, @strong grab_offset => move |_, x, _| {
+                let (left, right) = viewer.overview_window();
+                if (left..=right).contains(&x) {
+                    grab_offset.set(x - left);
+                } else {
+                    grab_offset.set((right - left) / 2.0);
+                }
+                viewer.pan_overview(x - grab_offset.get());
+            }),
+        );
+        drag.connect_drag_update(
+            glib::clone!(@weak viewer, @strong grab_offset => move |gesture, dx, _| {
+                if let Some((x, _)) = gesture.start_point() {
+                    viewer.pan_overview(x + dx - grab_offset.get());
+                }
+            }),
+        );
+        self.overview_area.add_controller(drag);
+    }
 
-    (MARGIN_SIDE * 2 + text_ext.width() as u64, ROW_HEIGHT)
-}
+    /// Left and right edges of the part of the dump in view, in `overview_area`.
+    fn overview_window(&self) -> (f64, f64) {
+        let (start_time, end_time) = self.view.get();
+        let dump_end = self.dump_end.get();
+        let width = self.overview_area.width();
+        let left = time_to_x(start_time, 0, dump_end, width);
+        let right = time_to_x(end_time, 0, dump_end, width);
+        (left, right.max(left + 2.0))
+    }
 
-fn draw_wave_value(cr: &gtk::cairo::Context, width: i32, _wdata: &WaveData) -> (u64, u64) {
-    let text = (width as u64 % 2).to_string().repeat(32);
-    let text_ext = cr.text_extents(&text).unwrap();
+    /// Moves the view, keeping its span, so that it starts at `x` in `overview_area`.
+    fn pan_overview(&self, x: f64) {
+        let (start_time, end_time) = self.view.get();
+        let span = end_time - start_time;
+        let dump_end = self.dump_end.get();
+        let start_time = x_to_time(x, 0, dump_end, self.overview_area.width())
+            .min(dump_end.saturating_sub(span));
+        self.view.set((start_time, start_time + span));
+        self.redraw();
+    }
 
-    cr.set_source_rgb(1.0, 1.0, 1.0);
-    cr.set_line_join(gtk::cairo::LineJoin::Bevel);
+    /// Draws how often the shown waves change along the whole dump, one bar per pixel. Bars grow
+    /// with the logarithm of the number of changes so that quiet stretches stay visible next to
+    /// busy ones.
+    fn draw_overview(&self, cr: &gtk::cairo::Context, width: i32, height: i32) {
+        let appearance = self.appearance.borrow();
+        let theme = &appearance.theme;
+        draw_background(cr, theme);
 
-    draw_text(cr, 0, width, Align::Right, &text);
-    cr.stroke().unwrap();
+        let dump_end = self.dump_end.get();
+        let mut waves = vec![];
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, row| {
+            if let RowKind::Wave(wdata) = &row.kind {
+                waves.push(wdata.data.clone());
+            }
+        });
+        let mut cache = self.overview_counts.borrow_mut();
+        if cache
+            .as_ref()
+            .is_some_and(|cache| !cache.is_for(width, dump_end, &waves))
+        {
+            *cache = None;
+        }
+        let counts = &cache
+            .get_or_insert_with(|| OverviewCounts::new(width, dump_end, waves))
+            .counts;
+        let max = counts.iter().copied().max().unwrap_or(0).max(1) as f64;
+        set_color(cr, theme.wave);
+        for (i, count) in counts.iter().enumerate() {
+            if *count > 0 {
+                let bar = (height - 2) as f64 * (*count as f64).ln_1p() / max.ln_1p();
+                cr.rectangle(i as f64, height as f64 - bar, 1.0, bar);
+            }
+        }
+        cr.fill().unwrap();
 
-    cr.set_source_rgb(0.0, 0.0, 0.4);
-    cr.move_to(0 as f64, ROW_HEIGHT as f64);
-    cr.line_to(width as f64, ROW_HEIGHT as f64);
-    cr.stroke().unwrap();
+        let (left, right) = self.overview_window();
+        cr.rectangle(left + 0.5, 0.5, right - left - 1.0, height as f64 - 1.0);
+        set_color(cr, theme.selection);
+        shade(cr);
+        set_color(cr, theme.cursor);
+        cr.stroke().unwrap();
 
-    (MARGIN_SIDE * 2 + text_ext.width() as u64, ROW_HEIGHT)
-}
+        set_color(cr, theme.separator);
+        cr.move_to(0.0, 0.5);
+        cr.line_to(width as f64, 0.5);
+        cr.stroke().unwrap();
+    }
 
-fn draw_wave(cr: &gtk::cairo::Context, width: i32, wdata: &WaveData) -> u64 {
-    let wave = &wdata.data;
-    let start_time: u64 = 0;
-    let end_time: u64 = 50000;
+    fn scroll_by(&self, dy: f64) {
+        self.vadjustment.set_value(self.vadjustment.value() + dy);
+    }
 
-    cr.set_source_rgb(1.0, 1.0, 1.0);
-    cr.set_line_join(gtk::cairo::LineJoin::Bevel);
+    /// Height of all shown rows together.
+    fn content_height(&self) -> u64 {
+        let density = self.appearance.borrow().density;
+        let mut height = 0;
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, row| {
+            height += row.height(density)
+        });
+        height
+    }
 
-    let window_iter = wave.windows(2);
-    for w in window_iter {
-        if let [a, b] = w {
-            if !(a.time > end_time || b.time < start_time) {
-                let section_start_time = cmp::max(start_time, a.time);
-                let section_end_time = cmp::min(end_time, b.time);
-
-                let section_left = (section_start_time - start_time) * (width as u64)
-                    / (end_time - start_time + 1);
-                let section_right = section_left
-                    + ((section_end_time - section_start_time) * (width as u64)
-                        / (end_time - start_time + 1));
-                let section_top = MARGIN_UP_DOWN;
-                let section_bottom = ROW_HEIGHT - MARGIN_UP_DOWN;
-
-                let is_value_changed = a.value != b.value;
-
-                let value_to_hline_y_pos = |val: &vcd::Value| -> u64 {
-                    match val {
-                        vcd::Value::V0 => section_top,
-                        vcd::Value::V1 => section_bottom,
-                        vcd::Value::X => section_top + ROW_HEIGHT / 2,
-                        vcd::Value::Z => section_top + ROW_HEIGHT / 2,
-                    }
-                };
+    /// Fits the range of `vadjustment` to the height of the rows and of `wave_area`, and
+    /// `hadjustment` to the view and the end of the dump.
+    fn update_scroll_range(&self) {
+        let (start_time, end_time) = self.view.get();
+        let span = (end_time - start_time) as f64;
+        let upper = self.dump_end.get().max(end_time) as f64;
+        self.hadjustment
+            .configure(start_time as f64, 0.0, upper, span / 10.0, span, span);
 
-                match (&a.value, &b.value) {
-                    (WaveValue::Scalar(v1), WaveValue::Scalar(v2)) => {
-                        cr.line_to(section_left as f64, value_to_hline_y_pos(v1) as f64);
-                        cr.line_to(section_right as f64, value_to_hline_y_pos(v1) as f64);
-                        cr.line_to(section_right as f64, value_to_hline_y_pos(v2) as f64);
-                    }
-                    (WaveValue::Vector(v1), WaveValue::Vector(_v2)) => {
-                        cr.line_to(section_left as f64, section_top as f64);
-                        cr.line_to(section_right as f64, section_top as f64);
-                        cr.stroke().unwrap();
+        let page = self.wave_area.height().max(1) as f64;
+        let upper = (self.content_height() as f64).max(page);
+        let step = self.appearance.borrow().density.row_height() as f64;
+        let adjustment = &self.vadjustment;
+        adjustment.configure(
+            adjustment.value().min(upper - page),
+            0.0,
+            upper,
+            step,
+            (page - step).max(step),
+            page,
+        );
+    }
 
-                        cr.move_to(section_left as f64, section_bottom as f64);
-                        cr.line_to(section_right as f64, section_bottom as f64);
-                        cr.stroke().unwrap();
+    /// Scrolls vertically as little as needed to show the row at display index `idx`.
+    pub fn scroll_to_row(&self, idx: usize) {
+        let density = self.appearance.borrow().density;
+        let mut span = None;
+        let mut i = 0;
+        let mut top = 0;
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, row| {
+            if i == idx {
+                span = Some((top as f64, (top + row.height(density)) as f64));
+            }
+            top += row.height(density);
+            i += 1;
+        });
+        let adjustment = &self.vadjustment;
+        match span {
+            Some((top, _)) if top < adjustment.value() => adjustment.set_value(top),
+            Some((_, bottom)) if bottom > adjustment.value() + adjustment.page_size() => {
+                adjustment.set_value(bottom - adjustment.page_size())
+            }
+            _ => (),
+        }
+    }
 
-                        if is_value_changed {
-                            cr.move_to(section_right as f64, section_top as f64);
-                            cr.line_to(section_right as f64, section_bottom as f64);
-                            cr.stroke().unwrap();
-                        }
+    /// Selects the first shown row whose name contains `text` and scrolls to it.
+    pub fn go_to_row(&self, text: &str) {
+        let mut found = None;
+        let mut i = 0;
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, row| {
+            let name = match &row.kind {
+                RowKind::Wave(wdata) => wdata.name.as_str(),
+                RowKind::Comment(text) => text,
+                RowKind::Group { name, .. } => name,
+                RowKind::Blank => "",
+            };
+            if found.is_none() && name.contains(text) {
+                found = Some(i);
+            }
+            i += 1;
+        });
+        if let Some(idx) = found {
+            self.select_row(idx, gdk::ModifierType::empty());
+            self.scroll_to_row(idx);
+        }
+    }
 
-                        cr.move_to((section_left + 2) as f64, (section_bottom - 2) as f64);
-                        cr.show_text(&v1.to_string()).ok();
-                        cr.stroke().unwrap();
-                    }
-                    (WaveValue::Custom(v1), WaveValue::Custom(_v2)) => {
-                        cr.line_to(section_left as f64, section_top as f64);
-                        cr.line_to(section_right as f64, section_top as f64);
-                        cr.stroke().unwrap();
+    /// Draws the title of a column above its rows.
+    fn draw_header(&self, cr: &gtk::cairo::Context, width: i32, title: &str) {
+        let appearance = self.appearance.borrow();
+        draw_background(cr, &appearance.theme);
+        set_color(cr, appearance.theme.text);
+        draw_text(
+            cr,
+            &appearance.font,
+            (width, RULER_HEIGHT),
+            Align::Left,
+            title,
+        );
+        draw_ruler_separator(cr, &appearance.theme, width);
+    }
 
-                        cr.move_to(section_left as f64, section_bottom as f64);
-                        cr.line_to(section_right as f64, section_bottom as f64);
-                        cr.stroke().unwrap();
+    /// Draws the times of the shown part of the dump above `wave_area`.
+    fn draw_ruler(&self, cr: &gtk::cairo::Context, width: i32) {
+        let appearance = self.appearance.borrow();
+        let theme = &appearance.theme;
+        draw_background(cr, theme);
 
-                        if is_value_changed {
-                            cr.move_to(section_right as f64, section_top as f64);
-                            cr.line_to(section_right as f64, section_bottom as f64);
-                            cr.stroke().unwrap();
-                        }
+        let (start_time, end_time) = self.view.get();
+        let step = tick_step(end_time - start_time + 1, width);
+        set_color(cr, theme.text);
+        let mut time = start_time.div_ceil(step) * step;
+        while time <= end_time {
+            let x = time_to_x(time, start_time, end_time, width).round() + 0.5;
+            cr.move_to(x, (RULER_HEIGHT - MARGIN_UP_DOWN) as f64);
+            cr.line_to(x, RULER_HEIGHT as f64);
+            cr.stroke().unwrap();
+            let layout = text_layout(cr, &appearance.font, &self.time_label(time));
+            cr.move_to(x + 2.0, 1.0);
+            pangocairo::functions::show_layout(cr, &layout);
+            time += step;
+        }
 
-                        cr.move_to((section_left + 2) as f64, (section_bottom - 2) as f64);
-                        cr.show_text(&v1.to_string()).ok();
-                        cr.stroke().unwrap();
-                    }
-                    _ => (),
-                }
+        if let Some(time) = self.cursor.get() {
+            if start_time <= time && time <= end_time {
+                let x = time_to_x(time, start_time, end_time, width);
+                set_color(cr, theme.cursor);
+                cr.move_to(x - 4.0, RULER_HEIGHT as f64 - 6.0);
+                cr.line_to(x + 4.0, RULER_HEIGHT as f64 - 6.0);
+                cr.line_to(x, RULER_HEIGHT as f64);
+                cr.close_path();
+                cr.fill().unwrap();
             }
         }
+        draw_ruler_separator(cr, theme, width);
     }
 
-    cr.stroke().unwrap();
+    /// Draws the name of each shown row, `scroll` pixels from the top, and returns the width of
+    /// the widest.
+    fn draw_names(&self, cr: &gtk::cairo::Context, width: i32, scroll: f64) -> u64 {
+        let appearance = self.appearance.borrow();
+        draw_background(cr, &appearance.theme);
 
-    cr.set_source_rgb(0.0, 0.0, 0.4);
-    cr.move_to(0 as f64, ROW_HEIGHT as f64);
-    cr.line_to(width as f64, ROW_HEIGHT as f64);
-    cr.stroke().unwrap();
+        let mut max_w: u64 = 0;
+        cr.save().unwrap();
+        cr.translate(0.0, -scroll);
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |depth, row| {
+            draw_row_background(cr, &appearance, width, row);
+            let (w, h) = draw_wave_name(cr, &appearance, width, depth, row);
+            cr.translate(0.0, h as f64);
+            max_w = u64::max(max_w, w);
+        });
+        cr.restore().unwrap();
+        max_w
+    }
 
-    ROW_HEIGHT
-}
+    /// Draws the value at the cursor of each shown row, `scroll` pixels from the top, and returns
+    /// the width of the widest.
+    fn draw_values(&self, cr: &gtk::cairo::Context, width: i32, scroll: f64) -> u64 {
+        let appearance = self.appearance.borrow();
+        draw_background(cr, &appearance.theme);
 
-fn get_wave<T: BufRead>(
-    id: &IdCode,
-    parser: &mut Parser<T>,
-) -> Result<Vec<WaveChangePoint>, Error> {
-    let mut current_time: SimTime = 0;
-    let mut wave: Vec<WaveChangePoint> = vec![];
-    while let Some(cmd) = parser.next().transpose()? {
-        match cmd {
-            Command::Timestamp(t) => {
-                current_time = t;
-            }
-            Command::ChangeScalar(i, v) if i == *id => {
-                wave.push(WaveChangePoint::new(current_time, WaveValue::Scalar(v)));
-            }
-            Command::ChangeVector(i, v) if i == *id => {
-                wave.push(WaveChangePoint::new(current_time, WaveValue::Vector(v)));
-            }
-            Command::ChangeReal(i, v) if i == *id => {
-                wave.push(WaveChangePoint::new(
-                    current_time,
-                    WaveValue::Custom(v.to_string()),
-                ));
+        let mut max_w: u64 = 0;
+        cr.save().unwrap();
+        cr.translate(0.0, -scroll);
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, row| {
+            draw_row_background(cr, &appearance, width, row);
+            let (w, h) = draw_wave_value(cr, &appearance, width, row, self.cursor.get());
+            cr.translate(0.0, h as f64);
+            max_w = u64::max(max_w, w);
+        });
+        cr.restore().unwrap();
+        max_w
+    }
+
+    /// Draws the waves of the shown rows, `scroll` pixels from the top, with the markers and the
+    /// cursor over them.
+    fn draw_waves(&self, cr: &gtk::cairo::Context, width: i32, height: i32, scroll: f64) {
+        let appearance = self.appearance.borrow();
+        let theme = &appearance.theme;
+        draw_background(cr, theme);
+
+        let (start_time, end_time) = self.view.get();
+        cr.save().unwrap();
+        cr.translate(0.0, -scroll);
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, row| {
+            draw_row_background(cr, &appearance, width, row);
+            let h = draw_wave(
+                cr,
+                &appearance,
+                width,
+                row,
+                start_time,
+                end_time,
+                self.dump_end.get(),
+            );
+            cr.translate(0.0, h as f64);
+        });
+        cr.restore().unwrap();
+
+        cr.save().unwrap();
+        set_color(cr, theme.marker);
+        cr.set_dash(&[4.0, 4.0], 0.0);
+        for marker in &self.markers.borrow().list {
+            if start_time <= marker.time && marker.time <= end_time {
+                let x = time_to_x(marker.time, start_time, end_time, width);
+                cr.move_to(x, 0.0);
+                cr.line_to(x, height as f64);
+                cr.stroke().unwrap();
+                cr.move_to(x + 2.0, 2.0);
+                pangocairo::functions::show_layout(
+                    cr,
+                    &text_layout(cr, &appearance.font, &marker.name),
+                );
             }
-            Command::ChangeString(i, v) if i == *id => {
-                wave.push(WaveChangePoint::new(
-                    current_time,
-                    WaveValue::Custom(v.to_string()),
-                ));
+        }
+        cr.restore().unwrap();
+
+        if let Some(time) = self.cursor.get() {
+            if start_time <= time && time <= end_time {
+                let x = time_to_x(time, start_time, end_time, width);
+                set_color(cr, theme.cursor);
+                cr.move_to(x, 0.0);
+                cr.line_to(x, height as f64);
+                cr.stroke().unwrap();
             }
-            _ => (),
         }
     }
-    Ok(wave)
-}
 
-fn extract_wave_from_vcd(filename: &str, path: Vec<String>) -> Result<WaveData, Error> {
-    let mut reader = Parser::new(BufReader::new(File::open(filename)?));
-    let header = reader.parse_header()?;
-    let var = &header.find_var(&path).unwrap();
-    let wave = get_wave(&var.code, &mut reader).expect("failed to get data");
-    Ok(WaveData::new(path.join("."), path, wave))
+    /// Saves the headers and all rows of the three columns side by side as a PNG image, drawn
+    /// with the current theme.
+    pub fn export_image(&self, filename: &Path) -> Result<(), Error> {
+        let widths = [
+            self.name_area.width(),
+            self.value_area.width(),
+            self.wave_area.width(),
+        ];
+        let rows_height = self.content_height() as i32;
+        let height = rows_height + RULER_HEIGHT as i32;
+        let mut surface = gtk::cairo::ImageSurface::create(
+            gtk::cairo::Format::ARgb32,
+            widths.iter().sum::<i32>().max(1),
+            height,
+        )
+        .map_err(Error::other)?;
+
+        let cr = gtk::cairo::Context::new(&surface).map_err(Error::other)?;
+        self.draw_header(&cr, widths[0], "Name");
+        cr.translate(0.0, RULER_HEIGHT as f64);
+        self.draw_names(&cr, widths[0], 0.0);
+        cr.translate(widths[0] as f64, -(RULER_HEIGHT as f64));
+        self.draw_header(&cr, widths[1], "Value");
+        cr.translate(0.0, RULER_HEIGHT as f64);
+        self.draw_values(&cr, widths[1], 0.0);
+        cr.translate(widths[1] as f64, -(RULER_HEIGHT as f64));
+        self.draw_ruler(&cr, widths[2]);
+        cr.translate(0.0, RULER_HEIGHT as f64);
+        self.draw_waves(&cr, widths[2], rows_height, 0.0);
+        drop(cr);
+
+        let (width, stride) = (surface.width(), surface.stride());
+        let data = surface.data().map_err(Error::other)?.to_vec();
+        // Cairo stores each pixel as a native-endian 32-bit ARGB value.
+        let format = if cfg!(target_endian = "little") {
+            gdk::MemoryFormat::B8g8r8a8Premultiplied
+        } else {
+            gdk::MemoryFormat::A8r8g8b8Premultiplied
+        };
+        gdk::MemoryTexture::new(
+            width,
+            height,
+            format,
+            &glib::Bytes::from_owned(data),
+            stride as usize,
+        )
+        .save_to_png(filename)
+        .map_err(Error::other)
+    }
+
+    fn setup_row_editing(self: &Rc<Self>) {
+        // Handlers only hold weak references so that they do not keep the viewer alive.
+        let viewer = self.clone();
+
+        let actions = &self.actions;
+        let add_action = |name: &str, f: fn(&Rc<WaveViewer>)| {
+            let action = gio::SimpleAction::new(name, None);
+            action.connect_activate(glib::clone!(@weak viewer => move |_, _| f(&viewer)));
+            actions.add_action(&action);
+        };
+        add_action("delete", |v| v.delete_selected_rows());
+        add_action("move-up", |v| v.move_selected_rows(true));
+        add_action("move-down", |v| v.move_selected_rows(false));
+        add_action("duplicate", |v| v.duplicate_selected_rows());
+        add_action("insert-blank", |v| v.insert_row(RowKind::Blank));
+        add_action("insert-comment", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "", move |text| {
+                viewer.insert_row(RowKind::Comment(text))
+            });
+        });
+        add_action("group", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "group", move |name| {
+                viewer.group_selected_rows(&name)
+            });
+        });
+        add_action("ungroup", |v| v.ungroup_selected_rows());
+        add_action("combine-msb-first", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "bus", move |name| {
+                if let Err(e) = viewer.combine_selected_rows(&name, true) {
+                    show_error(&viewer.pane, &format!("Cannot combine into a bus: {}", e));
+                }
+            });
+        });
+        add_action("combine-lsb-first", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "bus", move |name| {
+                if let Err(e) = viewer.combine_selected_rows(&name, false) {
+                    show_error(&viewer.pane, &format!("Cannot combine into a bus: {}", e));
+                }
+            });
+        });
+        add_action("add-expression", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "", move |text| {
+                if let Err(e) = viewer.add_expression(&text) {
+                    show_error(&viewer.pane, &format!("Invalid expression: {}", e));
+                }
+            });
+        });
+        add_action("go-to-row", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "", move |text| viewer.go_to_row(&text));
+        });
+        add_action("set-clock", |v| v.set_clock_from_selection());
+        add_action("go-start", |v| v.set_cursor(0));
+        add_action("go-end", |v| v.set_cursor(v.dump_end.get()));
+        add_action("previous-change", |v| v.jump_to_change(false));
+        add_action("next-change", |v| v.jump_to_change(true));
+        add_action("undo", |v| v.undo());
+        add_action("redo", |v| v.redo());
+        add_action("add-marker", |v| v.add_marker());
+        add_action("move-marker", |v| v.move_selected_marker());
+        add_action("previous-marker", |v| v.jump_to_marker(false));
+        add_action("next-marker", |v| v.jump_to_marker(true));
+        add_action("zoom-in", |v| v.zoom(0.5));
+        add_action("zoom-out", |v| v.zoom(2.0));
+        add_action("zoom-fit", |v| v.zoom_fit());
+        add_action("page-up", |v| v.scroll_by(-v.vadjustment.page_increment()));
+        add_action("page-down", |v| v.scroll_by(v.vadjustment.page_increment()));
+        add_action("scroll-top", |v| {
+            v.vadjustment.set_value(v.vadjustment.lower())
+        });
+        add_action("scroll-bottom", |v| {
+            v.vadjustment.set_value(v.vadjustment.upper())
+        });
+        add_action("find", |v| {
+            v.search_entry.grab_focus();
+        });
+        add_action("find-next", |v| v.find_match(true));
+        add_action("find-previous", |v| v.find_match(false));
+        add_action("radix-binary", |v| {
+            v.update_selected_waves(|row| row.radix = Radix::Binary)
+        });
+        add_action("radix-octal", |v| {
+            v.update_selected_waves(|row| row.radix = Radix::Octal)
+        });
+        add_action("radix-hex", |v| {
+            v.update_selected_waves(|row| row.radix = Radix::Hex)
+        });
+        add_action("radix-unsigned", |v| {
+            v.update_selected_waves(|row| row.radix = Radix::Unsigned)
+        });
+        add_action("radix-signed", |v| {
+            v.update_selected_waves(|row| row.radix = Radix::Signed)
+        });
+        add_action("digital", |v| {
+            v.update_selected_waves(|row| row.analog = None)
+        });
+        add_action("analog-step", |v| {
+            v.update_selected_waves(|row| {
+                row.analog
+                    .get_or_insert_with(AnalogStyle::default)
+                    .interpolation = Interpolation::Step
+            })
+        });
+        add_action("analog-linear", |v| {
+            v.update_selected_waves(|row| {
+                row.analog
+                    .get_or_insert_with(AnalogStyle::default)
+                    .interpolation = Interpolation::Linear
+            })
+        });
+        add_action("analog-range", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "auto", move |text| {
+                let range = match text.split_whitespace().collect::<Vec<_>>()[..] {
+                    ["auto"] => None,
+                    [min, max] => match (min.parse::<f64>(), max.parse::<f64>()) {
+                        (Ok(min), Ok(max)) if min < max => Some((min, max)),
+                        _ => return,
+                    },
+                    _ => return,
+                };
+                viewer.update_selected_waves(|row| {
+                    row.analog.get_or_insert_with(AnalogStyle::default).range = range
+                });
+            });
+        });
+        add_action("set-height", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "auto", move |text| {
+                let height = match text.trim() {
+                    "auto" => None,
+                    text => match text.parse::<u64>() {
+                        Ok(height) => Some(height.max(MIN_ROW_HEIGHT)),
+                        Err(_) => return,
+                    },
+                };
+                viewer.set_selected_row_height(height);
+            });
+        });
+        add_action("set-color", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "default", move |text| {
+                let color = match text.trim() {
+                    "default" => None,
+                    text => match parse_color(text) {
+                        Some(color) => Some(color),
+                        None => return,
+                    },
+                };
+                viewer.update_selected_waves(|row| row.style.color = color);
+            });
+        });
+        add_action("set-line-width", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "1", move |text| {
+                if let Ok(line_width) = text.trim().parse::<f64>() {
+                    if line_width > 0.0 {
+                        viewer.update_selected_waves(|row| row.style.line_width = line_width);
+                    }
+                }
+            });
+        });
+        add_action("toggle-fill", |v| {
+            v.update_selected_waves(|row| row.style.fill = !row.style.fill)
+        });
+        add_action("add-color-rule", |v| {
+            let viewer = v.clone();
+            prompt_text(&v.name_area, "red ", move |text| {
+                let Some((color, condition)) = text.trim().split_once(' ') else {
+                    return;
+                };
+                let result = match parse_color(color) {
+                    Some(color) => viewer.add_color_rule(color, condition.trim()),
+                    None => Err(format!("unknown color '{}'", color)),
+                };
+                if let Err(e) = result {
+                    show_error(&viewer.pane, &format!("Invalid color rule: {}", e));
+                }
+            });
+        });
+        add_action("clear-color-rules", |v| {
+            v.update_selected_waves(|row| row.style.rules.clear())
+        });
+        add_action("load-palette", |v| {
+            let viewer = v.clone();
+            choose_file(
+                &v.pane,
+                "Load Palette",
+                gtk::FileChooserAction::Open,
+                move |path| match Theme::load(&path) {
+                    Ok(theme) => viewer.set_theme(theme),
+                    Err(e) => show_error(&viewer.pane, &format!("Failed to load palette: {}", e)),
+                },
+            );
+        });
+        add_action("export-image", |v| {
+            let viewer = v.clone();
+            choose_file(
+                &v.pane,
+                "Export Image",
+                gtk::FileChooserAction::Save,
+                move |path| {
+                    if let Err(e) = viewer.export_image(&path) {
+                        show_error(&viewer.pane, &format!("Failed to export image: {}", e));
+                    }
+                },
+            );
+        });
+        add_action("save-session", |v| {
+            let viewer = v.clone();
+            choose_file(
+                &v.pane,
+                "Save Session",
+                gtk::FileChooserAction::Save,
+                move |path| {
+                    if let Err(e) = viewer.save_session(&path) {
+                        show_error(&viewer.pane, &format!("Failed to save session: {}", e));
+                    }
+                },
+            );
+        });
+        add_action("open-session", |v| {
+            let viewer = v.clone();
+            choose_file(
+                &v.pane,
+                "Open Session",
+                gtk::FileChooserAction::Open,
+                move |path| {
+                    if let Err(e) = viewer.load_session(&path) {
+                        show_error(&viewer.pane, &format!("Failed to load session: {}", e));
+                    }
+                },
+            );
+        });
+        self.pane.insert_action_group("rows", Some(actions));
+        self.update_history_actions();
+
+        let menu = gio::Menu::new();
+        let history_section = gio::Menu::new();
+        history_section.append(Some("Undo"), Some("rows.undo"));
+        history_section.append(Some("Redo"), Some("rows.redo"));
+        menu.append_section(None, &history_section);
+        menu.append(Some("Delete"), Some("rows.delete"));
+        menu.append(Some("Move Up"), Some("rows.move-up"));
+        menu.append(Some("Move Down"), Some("rows.move-down"));
+        menu.append(Some("Duplicate"), Some("rows.duplicate"));
+        let insert_section = gio::Menu::new();
+        insert_section.append(Some("Insert Blank Row"), Some("rows.insert-blank"));
+        insert_section.append(Some("Insert Comment…"), Some("rows.insert-comment"));
+        menu.append_section(None, &insert_section);
+        let navigate_section = gio::Menu::new();
+        navigate_section.append(Some("Go to Row…"), Some("rows.go-to-row"));
+        menu.append_section(None, &navigate_section);
+        let group_section = gio::Menu::new();
+        group_section.append(Some("Group Selected…"), Some("rows.group"));
+        group_section.append(Some("Ungroup"), Some("rows.ungroup"));
+        menu.append_section(None, &group_section);
+        let bus_section = gio::Menu::new();
+        bus_section.append(
+            Some("Combine into Bus, First Row as MSB…"),
+            Some("rows.combine-msb-first"),
+        );
+        bus_section.append(
+            Some("Combine into Bus, First Row as LSB…"),
+            Some("rows.combine-lsb-first"),
+        );
+        bus_section.append(Some("Add Derived Signal…"), Some("rows.add-expression"));
+        menu.append_section(None, &bus_section);
+        let display_menu = gio::Menu::new();
+        display_menu.append(Some("Digital"), Some("rows.digital"));
+        display_menu.append(Some("Analog, Step"), Some("rows.analog-step"));
+        display_menu.append(Some("Analog, Linear"), Some("rows.analog-linear"));
+        display_menu.append(
+            Some("Analog Range (“auto” or “min max”)…"),
+            Some("rows.analog-range"),
+        );
+        display_menu.append(
+            Some("Row Height (“auto” or pixels)…"),
+            Some("rows.set-height"),
+        );
+        let density_section = gio::Menu::new();
+        density_section.append(Some("Compact Rows"), Some("win.density-compact"));
+        density_section.append(Some("Normal Rows"), Some("win.density-normal"));
+        density_section.append(Some("Tall Rows"), Some("win.density-tall"));
+        display_menu.append_section(None, &density_section);
+        display_menu.append(Some("Font (such as “Monospace 10”)…"), Some("win.set-font"));
+        menu.append_submenu(Some("Display"), &display_menu);
+        let style_menu = gio::Menu::new();
+        style_menu.append(
+            Some("Color (name, #rrggbb or “default”)…"),
+            Some("rows.set-color"),
+        );
+        style_menu.append(Some("Line Width…"), Some("rows.set-line-width"));
+        style_menu.append(Some("Shade"), Some("rows.toggle-fill"));
+        style_menu.append(
+            Some("Add Color Rule (“color condition”)…"),
+            Some("rows.add-color-rule"),
+        );
+        style_menu.append(Some("Clear Color Rules"), Some("rows.clear-color-rules"));
+        menu.append_submenu(Some("Style"), &style_menu);
+        let theme_menu = gio::Menu::new();
+        theme_menu.append(Some("Dark"), Some("win.theme-dark"));
+        theme_menu.append(Some("Light"), Some("win.theme-light"));
+        theme_menu.append(Some("High Contrast"), Some("win.theme-high-contrast"));
+        theme_menu.append(Some("Print"), Some("win.theme-print"));
+        theme_menu.append(Some("Load Palette…"), Some("rows.load-palette"));
+        menu.append_submenu(Some("Theme"), &theme_menu);
+        let radix_menu = gio::Menu::new();
+        radix_menu.append(Some("Binary"), Some("rows.radix-binary"));
+        radix_menu.append(Some("Octal"), Some("rows.radix-octal"));
+        radix_menu.append(Some("Hexadecimal"), Some("rows.radix-hex"));
+        radix_menu.append(Some("Unsigned Decimal"), Some("rows.radix-unsigned"));
+        radix_menu.append(Some("Signed Decimal"), Some("rows.radix-signed"));
+        menu.append_submenu(Some("Radix"), &radix_menu);
+        let marker_section = gio::Menu::new();
+        marker_section.append(Some("Use as Clock for Markers"), Some("rows.set-clock"));
+        menu.append_section(None, &marker_section);
+        let session_section = gio::Menu::new();
+        session_section.append(Some("Open Session…"), Some("rows.open-session"));
+        session_section.append(Some("Save Session…"), Some("rows.save-session"));
+        session_section.append(Some("Export Image…"), Some("rows.export-image"));
+        menu.append_section(None, &session_section);
+        let context_menu = gtk::PopoverMenu::from_model(Some(&menu));
+        context_menu.set_parent(&self.name_area);
+        context_menu.set_has_arrow(false);
+
+        let click = gtk::GestureClick::builder().button(0).build();
+        click.connect_pressed(glib::clone!(@weak viewer => move |gesture, n_press, x, y| {
+            viewer.name_area.grab_focus();
+            let idx = viewer.row_at(y);
+            let state = gesture.current_event_state();
+            match gesture.current_button() {
+                gdk::BUTTON_SECONDARY => {
+                    if let Some(idx) = idx {
+                        if !viewer.is_row_selected(idx) {
+                            viewer.select_row(idx, gdk::ModifierType::empty());
+                        }
+                    }
+                    context_menu.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                    context_menu.popup();
+                }
+                gdk::BUTTON_PRIMARY => match idx {
+                    Some(idx) if viewer.is_on_expander(idx, x) => viewer.toggle_expanded(idx),
+                    Some(idx) if n_press == 2 => viewer.activate_row(idx),
+                    Some(idx) => viewer.select_row(idx, state),
+                    None => viewer.clear_selection(),
+                },
+                _ => (),
+            }
+        }));
+        self.name_area.add_controller(click);
+    }
+
+    fn setup_search(self: &Rc<Self>) {
+        let viewer = self.clone();
+
+        self.search_entry
+            .connect_activate(glib::clone!(@weak viewer => move |_| viewer.find_match(true)));
+        self.search_entry
+            .connect_next_match(glib::clone!(@weak viewer => move |_| viewer.find_match(true)));
+        self.search_entry.connect_previous_match(
+            glib::clone!(@weak viewer => move |_| viewer.find_match(false)),
+        );
+        let prev_button = gtk::Button::from_icon_name("go-up-symbolic");
+        prev_button.set_tooltip_text(Some("Previous Match"));
+        prev_button
+            .connect_clicked(glib::clone!(@weak viewer => move |_| viewer.find_match(false)));
+        let next_button = gtk::Button::from_icon_name("go-down-symbolic");
+        next_button.set_tooltip_text(Some("Next Match"));
+        next_button.connect_clicked(glib::clone!(@weak viewer => move |_| viewer.find_match(true)));
+
+        self.match_list
+            .connect_row_activated(glib::clone!(@weak viewer => move |_, row| {
+                let time = viewer.search_matches.borrow().1.get(row.index() as usize).copied();
+                if let Some(time) = time {
+                    viewer.set_cursor(time);
+                }
+                viewer.match_button.popdown();
+            }));
+        self.match_button.set_popover(Some(
+            &gtk::Popover::builder()
+                .child(
+                    &gtk::ScrolledWindow::builder()
+                        .child(&self.match_list)
+                        .hscrollbar_policy(gtk::PolicyType::Never)
+                        .min_content_height(200)
+                        .build(),
+                )
+                .build(),
+        ));
+
+        let search_bar = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .build();
+        search_bar.append(&self.search_entry);
+        search_bar.append(&prev_button);
+        search_bar.append(&next_button);
+        search_bar.append(&self.match_button);
+        self.pane.prepend(&search_bar);
+
+        let click = gtk::GestureClick::new();
+        click.connect_pressed(glib::clone!(@weak viewer => move |gesture, _, x, y| {
+            viewer.wave_area.grab_focus();
+            let (start_time, end_time) = viewer.view.get();
+            let width = viewer.wave_area.width();
+            let time = x_to_time(x, start_time, end_time, width);
+            // Holding Shift places the cursor exactly where clicked.
+            let state = gesture.current_event_state();
+            let time = if state.contains(gdk::ModifierType::SHIFT_MASK) {
+                time
+            } else {
+                viewer.snap_to_edge(x, y).unwrap_or(time)
+            };
+            viewer.set_cursor(time);
+        }));
+        self.wave_area.add_controller(click);
+    }
+
+    /// The change of the wave under (`x`, `y`) in `wave_area` that is closest to `x`, if it is
+    /// drawn within `SNAP_DISTANCE` pixels of it.
+    fn snap_to_edge(&self, x: f64, y: f64) -> Option<SimTime> {
+        let idx = self.row_at(y)?;
+        let rows = self.rows.borrow();
+        let (_, row) = rows::visible_row(&rows, idx)?;
+        let RowKind::Wave(wdata) = &row.kind else {
+            return None;
+        };
+        let (start_time, end_time) = self.view.get();
+        let width = self.wave_area.width();
+        let from = x_to_time(x - SNAP_DISTANCE, start_time, end_time, width);
+        let to = x_to_time(x + SNAP_DISTANCE, start_time, end_time, width) + 1;
+        let first = wdata.data.partition_point(|cp| cp.time < from);
+        wdata.data[first..]
+            .iter()
+            .take_while(|cp| cp.time <= to)
+            .map(|cp| {
+                let distance = (time_to_x(cp.time, start_time, end_time, width) - x).abs();
+                (cp.time, distance)
+            })
+            .filter(|(_, distance)| *distance <= SNAP_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(time, _)| time)
+    }
+
+    fn setup_markers(self: &Rc<Self>) {
+        let viewer = self.clone();
+
+        let add_button = gtk::Button::from_icon_name("list-add-symbolic");
+        add_button.set_tooltip_text(Some("Add Marker at Cursor (M)"));
+        add_button.connect_clicked(glib::clone!(@weak viewer => move |_| viewer.add_marker()));
+        let remove_button = gtk::Button::from_icon_name("list-remove-symbolic");
+        remove_button.set_tooltip_text(Some("Remove Marker"));
+        remove_button.connect_clicked(glib::clone!(@weak viewer => move |_| {
+            if let Some(row) = viewer.marker_list.selected_row() {
+                viewer.remove_marker(row.index() as usize);
+            }
+        }));
+        let move_button = gtk::Button::from_icon_name("go-jump-symbolic");
+        move_button.set_tooltip_text(Some("Move Marker to Cursor"));
+        move_button.set_action_name(Some("rows.move-marker"));
+        let rename_button = gtk::Button::from_icon_name("document-edit-symbolic");
+        rename_button.set_tooltip_text(Some("Rename Marker"));
+        rename_button.connect_clicked(glib::clone!(@weak viewer => move |_| {
+            if let Some(row) = viewer.marker_list.selected_row() {
+                let idx = row.index() as usize;
+                let name = viewer.markers.borrow().list[idx].name.clone();
+                let editor = viewer.clone();
+                prompt_text(&viewer.marker_list, &name, move |name| {
+                    editor.rename_marker(idx, name)
+                });
+            }
+        }));
+        self.marker_list
+            .connect_row_activated(glib::clone!(@weak viewer => move |_, row| {
+                let time = viewer.markers.borrow().list.get(row.index() as usize).map(|m| m.time);
+                if let Some(time) = time {
+                    viewer.set_cursor(time);
+                }
+            }));
+
+        let buttons = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .build();
+        buttons.append(&add_button);
+        buttons.append(&remove_button);
+        buttons.append(&rename_button);
+        buttons.append(&move_button);
+        self.marker_panel.append(&buttons);
+        self.marker_panel.append(&self.clock_label);
+        self.marker_panel.append(
+            &gtk::ScrolledWindow::builder()
+                .child(&self.marker_list)
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .vexpand(true)
+                .build(),
+        );
+        self.marker_panel.append(&gtk::Label::new(Some("Deltas")));
+        self.marker_panel.append(
+            &gtk::ScrolledWindow::builder()
+                .child(&self.delta_grid)
+                .vexpand(true)
+                .build(),
+        );
+
+        self.update_markers();
+    }
+
+    /// Adds a marker at the cursor.
+    pub fn add_marker(&self) {
+        if let Some(time) = self.cursor.get() {
+            self.record_edit();
+            self.markers.borrow_mut().add(time);
+            self.update_markers();
+        }
+    }
+
+    pub fn remove_marker(&self, idx: usize) {
+        self.record_edit();
+        self.markers.borrow_mut().remove(idx);
+        self.update_markers();
+    }
+
+    pub fn rename_marker(&self, idx: usize, name: String) {
+        self.record_edit();
+        self.markers.borrow_mut().rename(idx, name);
+        self.update_markers();
+    }
+
+    /// Moves the marker selected in the marker panel to the cursor.
+    pub fn move_selected_marker(&self) {
+        let (Some(row), Some(time)) = (self.marker_list.selected_row(), self.cursor.get()) else {
+            return;
+        };
+        self.record_edit();
+        let idx = self
+            .markers
+            .borrow_mut()
+            .move_to(row.index() as usize, time);
+        self.update_markers();
+        if let Some(row) = self.marker_list.row_at_index(idx as i32) {
+            self.marker_list.select_row(Some(&row));
+        }
+    }
+
+    /// Moves the cursor to the next or previous marker.
+    pub fn jump_to_marker(&self, forward: bool) {
+        let cursor = self.cursor.get().unwrap_or(0);
+        let markers = self.markers.borrow();
+        let marker = if forward {
+            markers.next_after(cursor)
+        } else {
+            markers.prev_before(cursor)
+        };
+        let target = marker.map(|m| m.time);
+        drop(markers);
+        if let Some(time) = target {
+            self.set_cursor(time);
+        }
+    }
+
+    /// Uses the first selected scalar wave as the clock whose rising edges are counted between
+    /// markers.
+    pub fn set_clock_from_selection(&self) {
+        let rows = self.rows.borrow();
+        let clock = rows::selected_waves(&rows)
+            .into_iter()
+            .find(|wdata| !wdata.is_vector());
+        if let Some(wdata) = clock {
+            let mut edges = vec![];
+            let mut was_high = false;
+            for cp in wdata.data.iter() {
+                let is_high = cp.value == WaveValue::Scalar(vcd::Value::V1);
+                if is_high && !was_high {
+                    edges.push(cp.time);
+                }
+                was_high = is_high;
+            }
+            *self.clock.borrow_mut() = Some((wdata.name.clone(), edges));
+        }
+        drop(rows);
+        self.update_markers();
+    }
+
+    /// Rebuilds the marker list and the table of deltas between each pair of markers.
+    fn update_markers(&self) {
+        let markers = self.markers.borrow();
+        let clock = self.clock.borrow();
+
+        self.clock_label.set_label(&match clock.as_ref() {
+            Some((name, _)) => format!("Clock: {}", name),
+            None => "Clock: none".to_string(),
+        });
+
+        while let Some(child) = self.marker_list.first_child() {
+            self.marker_list.remove(&child);
+        }
+        for marker in &markers.list {
+            let label = gtk::Label::builder()
+                .label(format!("{}\t{}", marker.name, self.time_label(marker.time)))
+                .xalign(0.0)
+                .build();
+            self.marker_list.append(&label);
+        }
+
+        while let Some(child) = self.delta_grid.first_child() {
+            self.delta_grid.remove(&child);
+        }
+        for (i, marker) in markers.list.iter().enumerate() {
+            let i = i as i32 + 1;
+            self.delta_grid
+                .attach(&gtk::Label::new(Some(&marker.name)), i, 0, 1, 1);
+            self.delta_grid
+                .attach(&gtk::Label::new(Some(&marker.name)), 0, i, 1, 1);
+        }
+        for (i, a) in markers.list.iter().enumerate() {
+            for (j, b) in markers.list.iter().enumerate().skip(i + 1) {
+                let mut text = self.time_label(b.time - a.time);
+                if let Some((_, edges)) = clock.as_ref() {
+                    text.push_str(&format!(
+                        "\n{} cycles",
+                        markers::cycles_between(edges, a.time, b.time)
+                    ));
+                }
+                self.delta_grid.attach(
+                    &gtk::Label::new(Some(&text)),
+                    j as i32 + 1,
+                    i as i32 + 1,
+                    1,
+                    1,
+                );
+            }
+        }
+
+        drop(markers);
+        drop(clock);
+        self.redraw();
+    }
+
+    /// Multiplies the span of the view by `factor`, keeping the cursor in place if it is in view
+    /// and the middle of the view otherwise.
+    pub fn zoom(&self, factor: f64) {
+        let (start_time, end_time) = self.view.get();
+        let span = end_time - start_time;
+        let center = match self.cursor.get() {
+            Some(cursor) if (start_time..=end_time).contains(&cursor) => cursor,
+            _ => start_time + span / 2,
+        };
+        // The view is kept within the dump, unless its end is not known.
+        let limit = match self.dump_end.get() {
+            0 => SimTime::MAX,
+            dump_end => dump_end.max(span),
+        };
+        let new_span = ((span as f64 * factor) as SimTime).clamp(1, limit);
+        let offset =
+            ((center - start_time) as f64 * new_span as f64 / span.max(1) as f64) as SimTime;
+        let start_time = center.saturating_sub(offset).min(limit - new_span);
+        self.view.set((start_time, start_time + new_span));
+        self.redraw();
+    }
+
+    /// Shows the whole dump.
+    pub fn zoom_fit(&self) {
+        self.view.set((0, self.dump_end.get().max(1)));
+        self.redraw();
+    }
+
+    /// Moves the cursor to the next or previous change of the selected waves, such as the next
+    /// trigger of a selected event.
+    pub fn jump_to_change(&self, forward: bool) {
+        let cursor = self.cursor.get();
+        let rows = self.rows.borrow();
+        let times = rows::selected_waves(&rows)
+            .into_iter()
+            .flat_map(|wdata| wdata.data.iter().map(|cp| cp.time));
+        let target = if forward {
+            times.filter(|time| cursor.is_none_or(|c| *time > c)).min()
+        } else {
+            times.filter(|time| cursor.is_some_and(|c| *time < c)).max()
+        };
+        drop(rows);
+        if let Some(time) = target {
+            self.set_cursor(time);
+        }
+    }
+
+    /// Places the cursor at `time`, scrolling the view to it if it is not shown.
+    pub fn set_cursor(&self, time: SimTime) {
+        self.cursor.set(Some(time));
+        let (start_time, end_time) = self.view.get();
+        if time < start_time || time > end_time {
+            let span = end_time - start_time;
+            let start_time = time.saturating_sub(span / 2);
+            self.view.set((start_time, start_time + span));
+        }
+        self.redraw();
+    }
+
+    /// Moves the cursor to the next or previous time at which the condition in the search entry
+    /// becomes true.
+    fn find_match(&self, forward: bool) {
+        let text = self.search_entry.text().to_string();
+        if text.trim().is_empty() {
+            return;
+        }
+        if self.search_matches.borrow().0 != text {
+            let matches = match self.search(&text) {
+                Ok(matches) => {
+                    self.search_entry.remove_css_class("error");
+                    self.search_entry.set_tooltip_text(None);
+                    matches
+                }
+                Err(e) => {
+                    self.search_entry.add_css_class("error");
+                    self.search_entry.set_tooltip_text(Some(&e));
+                    vec![]
+                }
+            };
+            self.show_matches(&matches);
+            *self.search_matches.borrow_mut() = (text, matches);
+        }
+
+        let cursor = self.cursor.get();
+        let matches = &self.search_matches.borrow().1;
+        let found = if forward {
+            matches
+                .iter()
+                .find(|time| cursor.is_none_or(|c| **time > c))
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|time| cursor.is_some_and(|c| **time < c))
+        };
+        if let Some(time) = found.copied() {
+            self.set_cursor(time);
+        }
+    }
+
+    /// Times at which the condition `text` becomes true.
+    fn search(&self, text: &str) -> Result<Vec<SimTime>, String> {
+        let parsed = expr::parse(text)?;
+        let paths: Vec<Vec<String>> = parsed
+            .signals()
+            .iter()
+            .map(|name| name.split('.').map(String::from).collect())
+            .collect();
+        let (waves, _) =
+            extract_waves_from_vcd(&self.filename, &paths).map_err(|e| e.to_string())?;
+        expr::find_true(&parsed, |name| {
+            waves.iter().find(|wdata| wdata.path.join(".") == name)
+        })
+    }
+
+    fn show_matches(&self, matches: &[SimTime]) {
+        while let Some(child) = self.match_list.first_child() {
+            self.match_list.remove(&child);
+        }
+        for time in matches {
+            let label = gtk::Label::builder()
+                .label(self.time_label(*time))
+                .xalign(0.0)
+                .build();
+            self.match_list.append(&label);
+        }
+        let label = match matches.len() {
+            0 => "No matches".to_string(),
+            1 => "1 match".to_string(),
+            n => format!("{} matches", n),
+        };
+        self.match_button.set_label(&label);
+    }
+
+    /// Display index of the row at `y` in the coordinates of the areas showing rows.
+    fn row_at(&self, y: f64) -> Option<usize> {
+        let y = y + self.vadjustment.value();
+        let density = self.appearance.borrow().density;
+        let mut found = None;
+        let mut idx = 0;
+        let mut top = 0.0;
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, row| {
+            let bottom = top + row.height(density) as f64;
+            if top <= y && y < bottom {
+                found = Some(idx);
+            }
+            top = bottom;
+            idx += 1;
+        });
+        found
+    }
+
+    fn is_row_selected(&self, idx: usize) -> bool {
+        rows::visible_row(&self.rows.borrow(), idx).is_some_and(|(_, row)| row.selected)
+    }
+
+    /// Whether `x` falls on the expand toggle drawn in front of the row's name.
+    fn is_on_expander(&self, idx: usize, x: f64) -> bool {
+        match rows::visible_row(&self.rows.borrow(), idx) {
+            Some((depth, row)) if row.is_expandable() => {
+                let left = (INDENT_WIDTH * depth as u64) as f64;
+                left <= x && x < left + (INDENT_WIDTH + MARGIN_SIDE) as f64
+            }
+            _ => false,
+        }
+    }
+
+    fn toggle_expanded(&self, idx: usize) {
+        if let Some(row) = rows::visible_row_mut(&mut self.rows.borrow_mut(), idx) {
+            rows::toggle_expanded(row);
+        }
+        self.redraw();
+    }
+
+    /// Updates the selection for a click on a row, following the usual list conventions:
+    /// Ctrl toggles the row and Shift extends the selection from the nearest selected row.
+    fn select_row(&self, idx: usize, state: gdk::ModifierType) {
+        let mut rows = self.rows.borrow_mut();
+        let selected = rows::selected_visible_indices(&rows);
+        if state.contains(gdk::ModifierType::CONTROL_MASK) {
+            let was_selected = selected.contains(&idx);
+            rows::set_selection(&mut rows, &|i| {
+                (i == idx && !was_selected) || (i != idx && selected.contains(&i))
+            });
+        } else if state.contains(gdk::ModifierType::SHIFT_MASK) {
+            let anchor = selected
+                .iter()
+                .copied()
+                .min_by_key(|i| i.abs_diff(idx))
+                .unwrap_or(idx);
+            rows::set_selection(&mut rows, &|i| {
+                cmp::min(anchor, idx) <= i && i <= cmp::max(anchor, idx)
+            });
+        } else {
+            rows::set_selection(&mut rows, &|i| i == idx);
+        }
+        drop(rows);
+        self.redraw();
+    }
+
+    fn clear_selection(&self) {
+        rows::set_selection(&mut self.rows.borrow_mut(), &|_| false);
+        self.redraw();
+    }
+
+    /// Handles a double click: comments are edited, and groups and vectors are collapsed or
+    /// expanded.
+    fn activate_row(self: &Rc<Self>, idx: usize) {
+        let text = match rows::visible_row(&self.rows.borrow(), idx) {
+            Some((_, row)) if row.is_expandable() => None,
+            Some((
+                _,
+                Row {
+                    kind: RowKind::Comment(text),
+                    ..
+                },
+            )) => Some(text.clone()),
+            _ => return,
+        };
+        match text {
+            Some(text) => self.edit_comment(idx, &text),
+            None => self.toggle_expanded(idx),
+        }
+    }
+
+    /// Applies `f` to every selected wave row, including the bits of expanded vectors.
+    pub fn update_selected_waves(&self, f: impl Fn(&mut Row)) {
+        let before = self.snapshot();
+        let settings = |row: &Row| (row.radix, row.analog, row.style.clone(), row.height);
+        let mut changed = false;
+        rows::for_each_selected_mut(&mut self.rows.borrow_mut(), &mut |row| {
+            if let RowKind::Wave(_) = row.kind {
+                let old = settings(row);
+                f(row);
+                changed |= settings(row) != old;
+            }
+        });
+        if changed {
+            self.record_snapshot(before);
+            self.redraw();
+        }
+    }
+
+    /// Sets the font of all text from a description such as `Monospace 10`.
+    pub fn set_font(&self, description: &str) {
+        self.appearance.borrow_mut().font = pango::FontDescription::from_string(description);
+        self.redraw();
+    }
+
+    /// Sets the height of the rows that have none of their own.
+    pub fn set_density(&self, density: Density) {
+        self.appearance.borrow_mut().density = density;
+        self.redraw();
+    }
+
+    /// Sets the height of the selected rows, or makes them follow the global preset again.
+    pub fn set_selected_row_height(&self, height: Option<u64>) {
+        let before = self.snapshot();
+        let mut changed = false;
+        rows::for_each_selected_mut(&mut self.rows.borrow_mut(), &mut |row| {
+            changed |= row.height != height;
+            row.height = height;
+        });
+        if changed {
+            self.record_snapshot(before);
+            self.redraw();
+        }
+    }
+
+    pub fn set_theme(&self, theme: Theme) {
+        self.appearance.borrow_mut().theme = theme;
+        self.redraw();
+    }
+
+    pub fn set_default_radix(&self, radix: Radix) {
+        self.default_radix.set(radix);
+    }
+
+    pub fn set_time_unit(&self, unit: Option<TimescaleUnit>) {
+        self.time_unit.set(unit);
+        // The marker panel shows times too.
+        self.update_markers();
+    }
+
+    fn time_label(&self, time: SimTime) -> String {
+        format_time(time, self.timescale, self.time_unit.get())
+    }
+
+    /// Width of the name column, and of the name and value columns together.
+    pub fn column_widths(&self) -> (i32, i32) {
+        (self.name_pane.position(), self.column_pane.position())
+    }
+
+    pub fn set_column_widths(&self, (names, columns): (i32, i32)) {
+        self.name_pane.set_position(names);
+        self.column_pane.set_position(columns);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            rows: self.rows.borrow().clone(),
+            markers: self.markers.borrow().clone(),
+        }
+    }
+
+    /// Keeps the rows and markers as they are, so that the edit about to be made can be undone.
+    fn record_edit(&self) {
+        self.record_snapshot(self.snapshot());
+    }
+
+    /// Keeps `snapshot`, taken before an edit that turned out to change something, so that the
+    /// edit can be undone.
+    fn record_snapshot(&self, snapshot: Snapshot) {
+        self.history.borrow_mut().record(snapshot);
+        self.update_history_actions();
+    }
+
+    pub fn undo(&self) {
+        let state = self.history.borrow_mut().undo(|| self.snapshot());
+        if let Some(state) = state {
+            self.restore(state);
+        }
+    }
+
+    pub fn redo(&self) {
+        let state = self.history.borrow_mut().redo(|| self.snapshot());
+        if let Some(state) = state {
+            self.restore(state);
+        }
+    }
+
+    fn restore(&self, Snapshot { rows, markers }: Snapshot) {
+        *self.rows.borrow_mut() = rows;
+        *self.markers.borrow_mut() = markers;
+        self.update_history_actions();
+        self.update_markers();
+    }
+
+    /// Disables undo and redo when there is nothing to undo or redo.
+    fn update_history_actions(&self) {
+        let history = self.history.borrow();
+        for (name, enabled) in [("undo", history.can_undo()), ("redo", history.can_redo())] {
+            if let Some(action) = self
+                .actions
+                .lookup_action(name)
+                .and_downcast::<gio::SimpleAction>()
+            {
+                action.set_enabled(enabled);
+            }
+        }
+    }
+
+    pub fn delete_selected_rows(&self) {
+        let before = self.snapshot();
+        if rows::delete_selected(&mut self.rows.borrow_mut()) {
+            self.record_snapshot(before);
+            self.redraw();
+        }
+    }
+
+    pub fn move_selected_rows(&self, up: bool) {
+        let before = self.snapshot();
+        if rows::move_selected(&mut self.rows.borrow_mut(), up) {
+            self.record_snapshot(before);
+            self.redraw();
+        }
+    }
+
+    pub fn duplicate_selected_rows(&self) {
+        let before = self.snapshot();
+        if rows::duplicate_selected(&mut self.rows.borrow_mut()) {
+            self.record_snapshot(before);
+            self.redraw();
+        }
+    }
+
+    /// Inserts a row after the last selected row, or at the end if nothing is selected.
+    pub fn insert_row(&self, kind: RowKind) {
+        self.record_edit();
+        rows::insert_after_selection(&mut self.rows.borrow_mut(), Row::new(kind));
+        self.redraw();
+    }
+
+    pub fn group_selected_rows(&self, name: &str) {
+        let before = self.snapshot();
+        if rows::group_selected(&mut self.rows.borrow_mut(), name) {
+            self.record_snapshot(before);
+            self.redraw();
+        }
+    }
+
+    pub fn ungroup_selected_rows(&self) {
+        let before = self.snapshot();
+        if rows::ungroup_selected(&mut self.rows.borrow_mut()) {
+            self.record_snapshot(before);
+            self.redraw();
+        }
+    }
+
+    /// Inserts a bus made of the selected scalar waves after the selection. The rows are taken
+    /// in display order, starting from the most or least significant bit as requested. Only
+    /// single-bit signals of the dump can be combined, since the bus is read back from the dump
+    /// by their paths.
+    pub fn combine_selected_rows(&self, name: &str, msb_first: bool) -> Result<(), String> {
+        let rows = self.rows.borrow();
+        let mut selected = rows::selected_waves(&rows);
+        if selected.is_empty() {
+            return Err("no signals are selected".to_string());
+        }
+        if let Some(wdata) = selected.iter().find(|wdata| {
+            wdata.source != WaveSource::Var
+                || wdata.size != 1
+                || matches!(
+                    wdata.var_type,
+                    Some(VarType::Real | VarType::String | VarType::Event)
+                )
+        }) {
+            return Err(format!(
+                "'{}' is not a single-bit signal of the dump",
+                wdata.name
+            ));
+        }
+        if !msb_first {
+            selected.reverse();
+        }
+        let bus = WaveData::from_bits(name.to_string(), &selected);
+        drop(rows);
+        self.record_edit();
+        rows::insert_after_selection(&mut self.rows.borrow_mut(), Row::new(RowKind::Wave(bus)));
+        self.redraw();
+        Ok(())
+    }
+
+    fn edit_comment(self: &Rc<Self>, idx: usize, text: &str) {
+        let viewer = self.clone();
+        prompt_text(&self.name_area, text, move |text| {
+            let before = viewer.snapshot();
+            match rows::visible_row_mut(&mut viewer.rows.borrow_mut(), idx) {
+                Some(row) if !matches!(&row.kind, RowKind::Comment(old) if *old == text) => {
+                    row.kind = RowKind::Comment(text)
+                }
+                _ => return,
+            }
+            viewer.record_snapshot(before);
+            viewer.redraw();
+        });
+    }
+
+    fn update_status(&self) {
+        let file = Path::new(&self.filename)
+            .file_name()
+            .map_or(self.filename.clone(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        let timescale = match self.timescale {
+            Some((n, unit)) => format!("{} {}", n, unit),
+            None => "none".to_string(),
+        };
+        let mut waves = 0;
+        rows::for_each_visible(&self.rows.borrow(), 0, &mut |_, row| {
+            if let RowKind::Wave(_) = row.kind {
+                waves += 1;
+            }
+        });
+        let time = |time: Option<SimTime>| match time {
+            Some(time) => self.time_label(time),
+            None => "–".to_string(),
+        };
+        self.status_label.set_label(&format!(
+            "{}   Timescale {}   End {}   {} signals   Cursor {}   Pointer {}",
+            file,
+            timescale,
+            self.time_label(self.dump_end.get()),
+            waves,
+            time(self.cursor.get()),
+            time(self.pointer.get())
+        ));
+    }
+
+    fn redraw(&self) {
+        self.update_status();
+        self.update_scroll_range();
+        self.name_header.queue_draw();
+        self.value_header.queue_draw();
+        self.ruler_area.queue_draw();
+        self.overview_area.queue_draw();
+        self.name_area.queue_draw();
+        self.value_area.queue_draw();
+        self.wave_area.queue_draw();
+    }
+}
+
+/// Shows a popover with a text entry below `parent` and calls `on_done` with the entered text
+/// when the user presses Enter.
+pub fn prompt_text(
+    parent: &impl IsA<gtk::Widget>,
+    initial: &str,
+    on_done: impl Fn(String) + 'static,
+) {
+    let entry = gtk::Entry::builder().text(initial).width_chars(30).build();
+    let popover = gtk::Popover::builder().child(&entry).build();
+    popover.set_parent(parent);
+    entry.connect_activate(glib::clone!(@weak popover => move |entry| {
+        on_done(entry.text().to_string());
+        popover.popdown();
+    }));
+    popover.connect_closed(|popover| {
+        glib::idle_add_local_once(glib::clone!(@weak popover => move || popover.unparent()));
+    });
+    popover.popup();
+    entry.grab_focus();
+}
+
+/// Shows a native file chooser for the window containing `parent` and calls `on_done` with the
+/// chosen path.
+pub fn choose_file(
+    parent: &impl IsA<gtk::Widget>,
+    title: &str,
+    action: gtk::FileChooserAction,
+    on_done: impl Fn(PathBuf) + 'static,
+) {
+    let window = parent.root().and_downcast::<gtk::Window>();
+    let dialog = gtk::FileChooserNative::new(Some(title), window.as_ref(), action, None, None);
+    // Native dialogs are not kept alive by GTK, so the handler holds the reference until the
+    // dialog is answered.
+    dialog.connect_response(glib::clone!(@strong dialog => move |_, response| {
+        if response == gtk::ResponseType::Accept {
+            if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                on_done(path);
+            }
+        }
+        dialog.destroy();
+    }));
+    dialog.show();
+}
+
+/// Shows `text` in an error dialog over the window containing `parent`.
+pub fn show_error(parent: &impl IsA<gtk::Widget>, text: &str) {
+    let window = parent.root().and_downcast::<gtk::Window>();
+    let dialog = gtk::MessageDialog::builder()
+        .modal(true)
+        .message_type(gtk::MessageType::Error)
+        .buttons(gtk::ButtonsType::Close)
+        .text(text)
+        .build();
+    dialog.set_transient_for(window.as_ref());
+    dialog.connect_response(|dialog, _| dialog.destroy());
+    dialog.present();
+}
+
+/// Parses a color name such as `red` or a specification such as `#ff0000`.
+fn parse_color(text: &str) -> Option<Color> {
+    let rgba = gdk::RGBA::parse(text).ok()?;
+    Some(Color(
+        rgba.red() as f64,
+        rgba.green() as f64,
+        rgba.blue() as f64,
+    ))
+}
+
+/// Paths of the signals read by the expression `text`, or none if it does not parse.
+fn signal_paths(text: &str) -> Vec<Vec<String>> {
+    match expr::parse(text) {
+        Ok(parsed) => parsed
+            .signals()
+            .iter()
+            .map(|name| name.split('.').map(String::from).collect())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Evaluates the condition of a color rule over `waves`, which must hold every signal it reads.
+fn condition_changes(
+    text: &str,
+    waves: &HashMap<Vec<String>, WaveData>,
+) -> Result<Vec<(SimTime, bool)>, String> {
+    let parsed = expr::parse(text)?;
+    expr::truth_changes(&parsed, |name| {
+        let path: Vec<String> = name.split('.').map(String::from).collect();
+        waves.get(&path)
+    })
+}
+
+fn draw_background(cr: &gtk::cairo::Context, theme: &Theme) {
+    set_color(cr, theme.background);
+    cr.paint().unwrap();
+}
+
+fn draw_row_background(cr: &gtk::cairo::Context, appearance: &Appearance, width: i32, row: &Row) {
+    if row.selected {
+        set_color(cr, appearance.theme.selection);
+        cr.rectangle(
+            0.0,
+            0.0,
+            width as f64,
+            row.height(appearance.density) as f64,
+        );
+        cr.fill().unwrap();
+    }
+}
+
+enum Align {
+    Right,
+    Left,
+}
+
+fn text_layout(
+    cr: &gtk::cairo::Context,
+    font: &pango::FontDescription,
+    text: &str,
+) -> pango::Layout {
+    let layout = pangocairo::functions::create_layout(cr);
+    layout.set_font_description(Some(font));
+    layout.set_text(text);
+    layout
+}
+
+/// Draws `text` centred vertically in a row `height` pixels tall and returns its width.
+fn draw_text(
+    cr: &gtk::cairo::Context,
+    font: &pango::FontDescription,
+    (width, height): (i32, u64),
+    align: Align,
+    text: &str,
+) -> u64 {
+    let layout = text_layout(cr, font, text);
+    let (text_width, text_height) = layout.pixel_size();
+    let y = (height as f64 - text_height as f64) / 2.0;
+    match align {
+        Align::Left => cr.move_to(MARGIN_SIDE as f64, y),
+        Align::Right => cr.move_to(width as f64 - MARGIN_SIDE as f64 - text_width as f64, y),
+    }
+    pangocairo::functions::show_layout(cr, &layout);
+    text_width.max(0) as u64
+}
+
+/// Draws `text` centred between `left` and `right` and around `middle`, cut short with "…" if it
+/// is too long. Nothing is drawn if not even "…" fits.
+fn draw_label(
+    cr: &gtk::cairo::Context,
+    font: &pango::FontDescription,
+    text: &str,
+    (left, right): (f64, f64),
+    middle: f64,
+) {
+    let available = right - left - 2.0 * MARGIN_SIDE as f64;
+    if available < text_layout(cr, font, "…").pixel_size().0 as f64 {
+        return;
+    }
+    let layout = text_layout(cr, font, text);
+    layout.set_width((available * pango::SCALE as f64) as i32);
+    layout.set_ellipsize(pango::EllipsizeMode::End);
+    let (text_width, text_height) = layout.pixel_size();
+    cr.move_to(
+        (left + right - text_width as f64) / 2.0,
+        middle - text_height as f64 / 2.0,
+    );
+    pangocairo::functions::show_layout(cr, &layout);
+}
+
+fn draw_wave_name(
+    cr: &gtk::cairo::Context,
+    appearance: &Appearance,
+    width: i32,
+    depth: usize,
+    row: &Row,
+) -> (u64, u64) {
+    let name = match &row.kind {
+        RowKind::Wave(wdata) => wdata.name.clone(),
+        RowKind::Blank => String::new(),
+        RowKind::Comment(text) => text.clone(),
+        RowKind::Group { name, .. } => name.clone(),
+    };
+    let text = match (row.is_expandable(), row.is_expanded()) {
+        (true, true) => format!("▾ {}", name),
+        (true, false) => format!("▸ {}", name),
+        (false, _) => name,
+    };
+    let indent = INDENT_WIDTH * depth as u64;
+
+    let theme = &appearance.theme;
+    match row.kind {
+        RowKind::Comment(_) => set_color(cr, theme.comment),
+        RowKind::Group { .. } => set_color(cr, theme.group),
+        _ => set_color(cr, theme.text),
+    }
+    cr.set_line_join(gtk::cairo::LineJoin::Bevel);
+
+    cr.save().unwrap();
+    cr.translate(indent as f64, 0.0);
+    let height = row.height(appearance.density);
+    let text_width = draw_text(cr, &appearance.font, (width, height), Align::Left, &text);
+    cr.restore().unwrap();
+
+    set_color(cr, theme.separator);
+    cr.move_to(0 as f64, height as f64);
+    cr.line_to(width as f64, height as f64);
+    cr.stroke().unwrap();
+
+    (indent + MARGIN_SIDE * 2 + text_width, height)
+}
+
+/// Draws the value of a wave at the cursor. Events show how many times they have been
+/// triggered by then, out of the total.
+fn draw_wave_value(
+    cr: &gtk::cairo::Context,
+    appearance: &Appearance,
+    width: i32,
+    row: &Row,
+    cursor: Option<SimTime>,
+) -> (u64, u64) {
+    let text = match (&row.kind, cursor) {
+        (RowKind::Wave(wdata), Some(time)) if wdata.is_event() => format!(
+            "{} of {}",
+            wdata.data.partition_point(|cp| cp.time <= time),
+            wdata.data.len()
+        ),
+        (RowKind::Wave(wdata), None) if wdata.is_event() => wdata.data.len().to_string(),
+        (RowKind::Wave(wdata), Some(time)) => wdata
+            .value_at(time)
+            .map(|value| format_value(value, wdata.size as usize, row.radix))
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+    set_color(cr, appearance.theme.text);
+    let height = row.height(appearance.density);
+    let text_width = draw_text(cr, &appearance.font, (width, height), Align::Right, &text);
+
+    set_color(cr, appearance.theme.separator);
+    cr.move_to(0 as f64, height as f64);
+    cr.line_to(width as f64, height as f64);
+    cr.stroke().unwrap();
+
+    (MARGIN_SIDE * 2 + text_width, height)
+}
+
+fn draw_ruler_separator(cr: &gtk::cairo::Context, theme: &Theme, width: i32) {
+    set_color(cr, theme.separator);
+    cr.move_to(0.0, RULER_HEIGHT as f64 - 0.5);
+    cr.line_to(width as f64, RULER_HEIGHT as f64 - 0.5);
+    cr.stroke().unwrap();
+}
+
+/// Time between ticks of the ruler: 1, 2 or 5 times a power of ten, chosen so that ticks are at
+/// least `RULER_TICK_SPACING` pixels apart when `span` is shown over `width` pixels.
+fn tick_step(span: SimTime, width: i32) -> SimTime {
+    let min_step = span as f64 * RULER_TICK_SPACING / width.max(1) as f64;
+    let mut power: SimTime = 1;
+    loop {
+        for step in [power, 2 * power, 5 * power] {
+            if step as f64 >= min_step {
+                return step;
+            }
+        }
+        power *= 10;
+    }
+}
+
+/// Horizontal position of `time` in an area `width` pixels wide showing `start_time` to
+/// `end_time`.
+fn time_to_x(time: SimTime, start_time: SimTime, end_time: SimTime, width: i32) -> f64 {
+    (time as f64 - start_time as f64) * width as f64 / (end_time - start_time + 1) as f64
+}
+
+fn x_to_time(x: f64, start_time: SimTime, end_time: SimTime, width: i32) -> SimTime {
+    let offset = x.max(0.0) * (end_time - start_time + 1) as f64 / width.max(1) as f64;
+    start_time + offset as SimTime
+}
+
+/// Consecutive change points of a wave as (change, end time, next value). The last change lasts
+/// until `dump_end`, the last timestamp in the dump.
+fn segments(
+    wave: &[WaveChangePoint],
+    dump_end: SimTime,
+) -> impl Iterator<Item = (&WaveChangePoint, SimTime, &WaveValue)> {
+    wave.iter()
+        .enumerate()
+        .map(move |(i, a)| match wave.get(i + 1) {
+            Some(b) => (a, b.time, &b.value),
+            None => (a, cmp::max(dump_end, a.time), &a.value),
+        })
+}
+
+fn draw_wave(
+    cr: &gtk::cairo::Context,
+    appearance: &Appearance,
+    width: i32,
+    row: &Row,
+    start_time: SimTime,
+    end_time: SimTime,
+    dump_end: SimTime,
+) -> u64 {
+    let (wave, is_event): (&[WaveChangePoint], bool) = match &row.kind {
+        RowKind::Wave(wdata) => (&wdata.data, wdata.is_event()),
+        _ => (&[], false),
+    };
+
+    cr.save().unwrap();
+    cr.set_line_width(row.style.line_width);
+    cr.set_line_join(gtk::cairo::LineJoin::Bevel);
+    set_color(cr, row.style.color.unwrap_or(appearance.theme.wave));
+    let height = row.height(appearance.density);
+    if let Some(style) = &row.analog {
+        draw_analog(
+            cr, appearance, width, row, style, start_time, end_time, dump_end,
+        );
+    } else if matches!(&row.kind, RowKind::Wave(_) if is_event) {
+        draw_events(
+            cr,
+            &appearance.theme,
+            (width, height),
+            wave,
+            &row.style,
+            start_time,
+            end_time,
+        );
+    } else {
+        draw_digital(cr, appearance, width, row, start_time, end_time, dump_end);
+    }
+    cr.restore().unwrap();
+
+    set_color(cr, appearance.theme.separator);
+    cr.move_to(0.0, height as f64);
+    cr.line_to(width as f64, height as f64);
+    cr.stroke().unwrap();
+
+    height
+}
+
+fn set_color(cr: &gtk::cairo::Context, Color(r, g, b): Color) {
+    cr.set_source_rgb(r, g, b);
+}
+
+/// Fills the current path with a translucent shade of the current color, keeping the path.
+fn shade(cr: &gtk::cairo::Context) {
+    cr.save().unwrap();
+    cr.clip_preserve();
+    cr.paint_with_alpha(0.3).unwrap();
+    cr.restore().unwrap();
+}
+
+/// Draws scalar waves as lines switching between two levels and other waves as a series of bus
+/// values.
+fn draw_digital(
+    cr: &gtk::cairo::Context,
+    appearance: &Appearance,
+    width: i32,
+    row: &Row,
+    start_time: SimTime,
+    end_time: SimTime,
+    dump_end: SimTime,
+) {
+    let (wave, size): (&[WaveChangePoint], usize) = match &row.kind {
+        RowKind::Wave(wdata) => (&wdata.data, wdata.size as usize),
+        _ => (&[], 0),
+    };
+
+    for (a, b_time, b_value) in segments(wave, dump_end) {
+        if !(a.time > end_time || b_time < start_time) {
+            let section_start_time = cmp::max(start_time, a.time);
+            let section_end_time = cmp::min(end_time, b_time);
+
+            let section_left =
+                (section_start_time - start_time) * (width as u64) / (end_time - start_time + 1);
+            let section_right = section_left
+                + ((section_end_time - section_start_time) * (width as u64)
+                    / (end_time - start_time + 1));
+            let section_top = MARGIN_UP_DOWN;
+            let section_bottom = row.height(appearance.density) - MARGIN_UP_DOWN;
+
+            let value_to_hline_y_pos = |val: &vcd::Value| -> u64 {
+                match val {
+                    vcd::Value::V0 => section_top,
+                    vcd::Value::V1 => section_bottom,
+                    vcd::Value::X => (section_top + section_bottom) / 2,
+                    vcd::Value::Z => (section_top + section_bottom) / 2,
+                }
+            };
+
+            // Each run is drawn whole but clipped to its time span, so that a color rule can
+            // change the color in the middle of a value.
+            let runs = row.style.color_runs(a.time, b_time);
+            for (i, (run_start, run_end, color)) in runs.iter().enumerate() {
+                let left = if i == 0 {
+                    0.0
+                } else {
+                    time_to_x(*run_start, start_time, end_time, width)
+                };
+                let right = if i + 1 == runs.len() {
+                    width as f64
+                } else {
+                    time_to_x(*run_end, start_time, end_time, width)
+                };
+                cr.save().unwrap();
+                let height = row.height(appearance.density) as f64;
+                cr.rectangle(left, 0.0, right - left, height);
+                cr.clip();
+                set_color(cr, color.unwrap_or(appearance.theme.wave));
+                match (&a.value, b_value) {
+                    (WaveValue::Scalar(v1), WaveValue::Scalar(v2)) => {
+                        if row.style.fill && *v1 == vcd::Value::V1 {
+                            cr.rectangle(
+                                section_left as f64,
+                                section_top as f64,
+                                (section_right - section_left) as f64,
+                                (section_bottom - section_top) as f64,
+                            );
+                            shade(cr);
+                            cr.new_path();
+                        }
+                        cr.move_to(section_left as f64, value_to_hline_y_pos(v1) as f64);
+                        cr.line_to(section_right as f64, value_to_hline_y_pos(v1) as f64);
+                        cr.line_to(section_right as f64, value_to_hline_y_pos(v2) as f64);
+                        cr.stroke().unwrap();
+                    }
+                    (v1, _) => {
+                        draw_bus_segment(
+                            cr,
+                            appearance,
+                            row,
+                            &format_value(v1, size, row.radix),
+                            bus_state(v1),
+                            (
+                                time_to_x(a.time, start_time, end_time, width),
+                                time_to_x(b_time, start_time, end_time, width),
+                            ),
+                            width,
+                        );
+                    }
+                }
+                cr.restore().unwrap();
+            }
+        }
+    }
+}
+
+enum BusState {
+    Valid,
+    /// Every bit is Z.
+    HighImpedance,
+    /// Some bits are X or Z.
+    Unknown,
+}
+
+fn bus_state(value: &WaveValue) -> BusState {
+    match value {
+        WaveValue::Vector(v) if v.iter().all(|bit| bit == vcd::Value::Z) => BusState::HighImpedance,
+        WaveValue::Vector(v)
+            if v.iter()
+                .any(|bit| matches!(bit, vcd::Value::X | vcd::Value::Z)) =>
+        {
+            BusState::Unknown
+        }
+        _ => BusState::Valid,
+    }
+}
+
+/// Draws one value of a bus from `x0` to `x1` as a hexagon whose pointed ends cross those of its
+/// neighbours. The crossings narrow as the segment gets shorter on screen, so that zooming out
+/// leaves room for the label. A high-impedance bus is drawn as a line through the middle of the
+/// row and an unknown one is filled in the unknown color of the theme. Other values are drawn in
+/// the current color, and shaded if the row asks for it.
+fn draw_bus_segment(
+    cr: &gtk::cairo::Context,
+    appearance: &Appearance,
+    row: &Row,
+    text: &str,
+    state: BusState,
+    (x0, x1): (f64, f64),
+    width: i32,
+) {
+    let top = MARGIN_UP_DOWN as f64;
+    let bottom = (row.height(appearance.density) - MARGIN_UP_DOWN) as f64;
+    let middle = (top + bottom) / 2.0;
+    let slope = BUS_SLOPE.min((x1 - x0) / 4.0);
+    // Segments can reach far outside the area when zoomed in; only their ends need to be kept.
+    let x0 = x0.max(-2.0 * BUS_SLOPE);
+    let x1 = x1.min(width as f64 + 2.0 * BUS_SLOPE);
+
+    cr.save().unwrap();
+    if let BusState::HighImpedance = state {
+        set_color(cr, appearance.theme.high_impedance);
+        cr.move_to(x0, middle);
+        cr.line_to(x1, middle);
+        cr.stroke().unwrap();
+        cr.restore().unwrap();
+        return;
+    }
+
+    cr.move_to(x0, middle);
+    cr.line_to(x0 + slope, top);
+    cr.line_to(x1 - slope, top);
+    cr.line_to(x1, middle);
+    cr.line_to(x1 - slope, bottom);
+    cr.line_to(x0 + slope, bottom);
+    cr.close_path();
+    if let BusState::Unknown = state {
+        set_color(cr, appearance.theme.unknown_fill);
+        cr.fill_preserve().unwrap();
+        set_color(cr, appearance.theme.unknown);
+    } else if row.style.fill {
+        shade(cr);
+    }
+    cr.stroke().unwrap();
+
+    draw_label(
+        cr,
+        &appearance.font,
+        text,
+        ((x0 + slope).max(0.0), (x1 - slope).min(width as f64)),
+        middle,
+    );
+    cr.new_path();
+    cr.restore().unwrap();
+}
+
+/// Formats `time`, counted in steps of `timescale`, in `unit` if there is one and in the unit of
+/// `timescale` otherwise, such as `1200 ps` or `1.2 ns`.
+fn format_time(
+    time: SimTime,
+    timescale: Option<(u32, TimescaleUnit)>,
+    unit: Option<TimescaleUnit>,
+) -> String {
+    match (timescale, unit) {
+        (Some((n, from)), Some(to)) if to != from => {
+            // `divisor` is the number of steps of a unit in one second.
+            let value = (time * n as SimTime) as f64 * to.divisor() as f64 / from.divisor() as f64;
+            // Six decimals show down to the femtoseconds of a time in nanoseconds, while the
+            // rounding errors of the conversion stay hidden.
+            let value = format!("{:.6}", value);
+            let value = value.trim_end_matches('0').trim_end_matches('.');
+            format!("{} {}", value, to)
+        }
+        (Some((n, unit)), _) => format!("{} {}", time * n as SimTime, unit),
+        (None, _) => time.to_string(),
+    }
+}
+
+fn read_timescale(filename: &str) -> Option<(u32, TimescaleUnit)> {
+    let mut reader = Parser::new(BufReader::new(File::open(filename).ok()?));
+    reader.parse_header().ok()?.timescale
+}
+
+/// The last timestamp in the dump, or 0 if it cannot be read.
+fn read_dump_end(filename: &str) -> SimTime {
+    extract_waves_from_vcd(filename, &[]).map_or(0, |(_, dump_end)| dump_end)
+}
+
+/// Draws an upward arrow at each trigger of an event.
+fn draw_events(
+    cr: &gtk::cairo::Context,
+    theme: &Theme,
+    (width, height): (i32, u64),
+    wave: &[WaveChangePoint],
+    wave_style: &WaveStyle,
+    start_time: SimTime,
+    end_time: SimTime,
+) {
+    let top = MARGIN_UP_DOWN as f64;
+    let bottom = (height - MARGIN_UP_DOWN) as f64;
+
+    for cp in wave {
+        if cp.time < start_time || cp.time > end_time {
+            continue;
+        }
+        set_color(cr, wave_style.color_at(cp.time).unwrap_or(theme.wave));
+        let x = time_to_x(cp.time, start_time, end_time, width);
+        cr.move_to(x, bottom);
+        cr.line_to(x, top);
+        cr.stroke().unwrap();
+        cr.move_to(x, top);
+        cr.line_to(x - 4.0, top + 6.0);
+        cr.line_to(x + 4.0, top + 6.0);
+        cr.close_path();
+        cr.fill().unwrap();
+    }
+}
+
+/// The value of a change point as a number for analog display. Vectors are read as unsigned.
+fn numeric_value(value: &WaveValue) -> Option<f64> {
+    match value {
+        WaveValue::Scalar(vcd::Value::V0) => Some(0.0),
+        WaveValue::Scalar(vcd::Value::V1) => Some(1.0),
+        WaveValue::Scalar(_) => None,
+        WaveValue::Vector(v) => v.iter().try_fold(0.0, |n, bit| match bit {
+            vcd::Value::V0 => Some(n * 2.0),
+            vcd::Value::V1 => Some(n * 2.0 + 1.0),
+            _ => None,
+        }),
+        WaveValue::Real(r) => Some(*r),
+        WaveValue::String(_) | WaveValue::Event => None,
+    }
+}
+
+/// Formats a value for display, showing vectors as `width` bits in `radix`.
+pub fn format_value(value: &WaveValue, width: usize, radix: Radix) -> String {
+    match value {
+        WaveValue::Scalar(v) => v.to_string(),
+        WaveValue::Vector(v) => {
+            let width = width.max(v.len());
+            let bits: Vec<vcd::Value> = (0..width).map(|pos| vector_bit(v, width, pos)).collect();
+            format_bits(&bits, radix)
+        }
+        WaveValue::Real(r) => r.to_string(),
+        WaveValue::String(s) => s.clone(),
+        WaveValue::Event => "event".to_string(),
+    }
+}
+
+/// Formats bits given most significant first. A digit covering an X or Z bit is shown as `x` or
+/// `z`, and decimal values with such bits as `x`. Vectors too wide for decimal are shown in hex.
+fn format_bits(bits: &[vcd::Value], radix: Radix) -> String {
+    let is_known = bits
+        .iter()
+        .all(|bit| matches!(bit, vcd::Value::V0 | vcd::Value::V1));
+    if matches!(radix, Radix::Unsigned | Radix::Signed) && bits.len() <= 128 {
+        if !is_known {
+            return "x".to_string();
+        }
+        let n = bits
+            .iter()
+            .fold(0u128, |n, bit| n << 1 | (*bit == vcd::Value::V1) as u128);
+        if radix == Radix::Signed {
+            // Moving the sign bit to the top lets the arithmetic shift back extend it.
+            let shift = 128 - bits.len().max(1) as u32;
+            return (((n << shift) as i128) >> shift).to_string();
+        }
+        return n.to_string();
+    }
+
+    let digit_width = match radix {
+        Radix::Binary => 1,
+        Radix::Octal => 3,
+        _ => 4,
+    };
+    let digits: Vec<char> = bits
+        .rchunks(digit_width)
+        .map(|chunk| {
+            if chunk.contains(&vcd::Value::X) {
+                'x'
+            } else if chunk.contains(&vcd::Value::Z) {
+                'z'
+            } else {
+                let d = chunk
+                    .iter()
+                    .fold(0, |d, bit| d * 2 + (*bit == vcd::Value::V1) as u32);
+                char::from_digit(d, 16).unwrap()
+            }
+        })
+        .collect();
+    digits.iter().rev().collect()
+}
+
+/// Draws a wave as a trace whose height follows its value, with the bounds of the scale in the
+/// corners. Values that are not numbers, such as X, leave a gap. The trace is drawn in the current
+/// color.
+#[allow(clippy::too_many_arguments)]
+fn draw_analog(
+    cr: &gtk::cairo::Context,
+    appearance: &Appearance,
+    width: i32,
+    row: &Row,
+    style: &AnalogStyle,
+    start_time: SimTime,
+    end_time: SimTime,
+    dump_end: SimTime,
+) {
+    let wave: &[WaveChangePoint] = match &row.kind {
+        RowKind::Wave(wdata) => &wdata.data,
+        _ => &[],
+    };
+    let values: Vec<Option<f64>> = wave.iter().map(|cp| numeric_value(&cp.value)).collect();
+    let (min, max) = style.range.unwrap_or_else(|| {
+        values
+            .iter()
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(*v), max.max(*v))
+            })
+    });
+    let top = MARGIN_UP_DOWN as f64;
+    let bottom = (row.height(appearance.density) - MARGIN_UP_DOWN) as f64;
+    if min > max {
+        return;
+    }
+
+    let y_of = |v: f64| {
+        if max > min {
+            bottom - (v.clamp(min, max) - min) / (max - min) * (bottom - top)
+        } else {
+            (top + bottom) / 2.0
+        }
+    };
+
+    let mut pen_down = false;
+    for (i, (a, b_time, _)) in segments(wave, dump_end).enumerate() {
+        if b_time < start_time || a.time > end_time {
+            pen_down = false;
+            continue;
+        }
+        let x0 = time_to_x(a.time, start_time, end_time, width);
+        let x1 = time_to_x(b_time, start_time, end_time, width);
+        let next = values.get(i + 1).copied().unwrap_or(values[i]);
+        match (values[i], next) {
+            (Some(v0), next) => {
+                let y0 = y_of(v0);
+                if !pen_down {
+                    cr.move_to(x0, y0);
+                }
+                match (style.interpolation, next) {
+                    (Interpolation::Linear, Some(v1)) => cr.line_to(x1, y_of(v1)),
+                    _ => {
+                        cr.line_to(x1, y0);
+                        if let Some(v1) = next {
+                            cr.line_to(x1, y_of(v1));
+                        }
+                    }
+                }
+                pen_down = next.is_some();
+            }
+            (None, _) => pen_down = false,
+        }
+    }
+    cr.stroke().unwrap();
+
+    set_color(cr, appearance.theme.scale);
+    cr.move_to(2.0, top);
+    pangocairo::functions::show_layout(cr, &text_layout(cr, &appearance.font, &max.to_string()));
+    let layout = text_layout(cr, &appearance.font, &min.to_string());
+    cr.move_to(2.0, bottom - layout.pixel_size().1 as f64);
+    pangocairo::functions::show_layout(cr, &layout);
+}
+
+/// Event variables are dumped as scalar changes, but only the times of their triggers matter.
+fn typed_value(var_type: Option<VarType>, value: WaveValue) -> WaveValue {
+    match var_type {
+        Some(VarType::Event) => WaveValue::Event,
+        _ => value,
+    }
+}
+
+/// Reads the changes of `var`.
+fn get_wave<T: BufRead>(var: &Var, parser: &mut Parser<T>) -> Result<Vec<WaveChangePoint>, Error> {
+    let id = &var.code;
+    let mut current_time: SimTime = 0;
+    let mut wave: Vec<WaveChangePoint> = vec![];
+    while let Some(cmd) = parser.next().transpose()? {
+        match cmd {
+            Command::Timestamp(t) => {
+                current_time = t;
+            }
+            Command::ChangeScalar(i, v) if i == *id => {
+                wave.push(WaveChangePoint::new(
+                    current_time,
+                    typed_value(Some(var.var_type), WaveValue::Scalar(v)),
+                ));
+            }
+            Command::ChangeVector(i, v) if i == *id => {
+                wave.push(WaveChangePoint::new(current_time, WaveValue::Vector(v)));
+            }
+            Command::ChangeReal(i, v) if i == *id => {
+                wave.push(WaveChangePoint::new(current_time, WaveValue::Real(v)));
+            }
+            Command::ChangeString(i, v) if i == *id => {
+                wave.push(WaveChangePoint::new(current_time, WaveValue::String(v)));
+            }
+            _ => (),
+        }
+    }
+    Ok(wave)
+}
+
+/// Looks up a variable by its path. A last component of the form `name[i]` also matches a
+/// variable declared as `name [i]`, as dumpers do for bit-blasted vectors.
+fn find_var<'a>(header: &'a Header, path: &[String]) -> Option<&'a Var> {
+    let (last, scope_path) = path.split_last()?;
+    let bit_select = last.strip_suffix(']').and_then(|s| s.split_once('['));
+    if let Some((reference, bit)) = bit_select {
+        let items = match scope_path {
+            [] => Some(&header.items),
+            _ => header.find_scope(scope_path).map(|scope| &scope.items),
+        };
+        let found = items.into_iter().flatten().find_map(|item| match item {
+            ScopeItem::Var(var)
+                if var.reference == reference
+                    && matches!(var.index, Some(ReferenceIndex::BitSelect(i)) if i.to_string() == bit) =>
+            {
+                Some(var)
+            }
+            _ => None,
+        });
+        if found.is_some() {
+            return found;
+        }
+    }
+    header.find_var(path)
+}
+
+fn extract_wave_from_vcd(filename: &str, path: Vec<String>) -> Result<WaveData, Error> {
+    let mut reader = Parser::new(BufReader::new(File::open(filename)?));
+    let header = reader.parse_header()?;
+    let var = find_var(&header, &path).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("no signal named '{}'", path.join(".")),
+        )
+    })?;
+    let wave = get_wave(var, &mut reader)?;
+    Ok(WaveData {
+        data: wave.into(),
+        ..WaveData::from_var(path, var)
+    })
+}
+
+/// Reads several signals in a single pass over the dump. Paths that do not name a variable are
+/// skipped. Also returns the last timestamp in the dump.
+fn extract_waves_from_vcd(
+    filename: &str,
+    paths: &[Vec<String>],
+) -> Result<(Vec<WaveData>, SimTime), Error> {
+    let mut reader = Parser::new(BufReader::new(File::open(filename)?));
+    let header = reader.parse_header()?;
+    let mut waves: Vec<WaveData> = vec![];
+    let mut data: Vec<Vec<WaveChangePoint>> = vec![];
+    let mut index_of: HashMap<IdCode, Vec<usize>> = HashMap::new();
+    for path in paths {
+        if let Some(var) = find_var(&header, path) {
+            index_of.entry(var.code).or_default().push(waves.len());
+            waves.push(WaveData::from_var(path.clone(), var));
+            data.push(vec![]);
+        }
+    }
+
+    let mut current_time: SimTime = 0;
+    while let Some(cmd) = reader.next().transpose()? {
+        let (id, value) = match cmd {
+            Command::Timestamp(t) => {
+                current_time = t;
+                continue;
+            }
+            Command::ChangeScalar(i, v) => (i, WaveValue::Scalar(v)),
+            Command::ChangeVector(i, v) => (i, WaveValue::Vector(v)),
+            Command::ChangeReal(i, v) => (i, WaveValue::Real(v)),
+            Command::ChangeString(i, v) => (i, WaveValue::String(v)),
+            _ => continue,
+        };
+        if let Some(indices) = index_of.get(&id) {
+            for idx in indices {
+                let value = typed_value(waves[*idx].var_type, value.clone());
+                data[*idx].push(WaveChangePoint::new(current_time, value));
+            }
+        }
+    }
+    for (wdata, data) in waves.iter_mut().zip(data) {
+        wdata.data = data.into();
+    }
+    Ok((waves, current_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bits written as in a dump, such as `10xz`.
+    fn bits(text: &str) -> Vec<vcd::Value> {
+        text.chars()
+            .map(|c| match c {
+                '0' => vcd::Value::V0,
+                '1' => vcd::Value::V1,
+                'x' => vcd::Value::X,
+                _ => vcd::Value::Z,
+            })
+            .collect()
+    }
+
+    fn vector(text: &str) -> vcd::Vector {
+        bits(text).into_iter().collect()
+    }
+
+    #[test]
+    fn decimal() {
+        assert_eq!(format_bits(&bits("1010"), Radix::Unsigned), "10");
+        assert_eq!(format_bits(&bits("1010"), Radix::Signed), "-6");
+        assert_eq!(format_bits(&bits("0110"), Radix::Signed), "6");
+        assert_eq!(format_bits(&bits("1"), Radix::Signed), "-1");
+        assert_eq!(format_bits(&bits("1"), Radix::Unsigned), "1");
+        let ones = "1".repeat(64);
+        assert_eq!(format_bits(&bits(&ones), Radix::Signed), "-1");
+        assert_eq!(
+            format_bits(&bits(&ones), Radix::Unsigned),
+            u64::MAX.to_string()
+        );
+        let min = format!("1{}", "0".repeat(127));
+        assert_eq!(
+            format_bits(&bits(&min), Radix::Signed),
+            i128::MIN.to_string()
+        );
+        assert_eq!(format_bits(&bits("10x0"), Radix::Unsigned), "x");
+        assert_eq!(format_bits(&bits("z000"), Radix::Signed), "x");
+    }
+
+    #[test]
+    fn digits() {
+        assert_eq!(format_bits(&bits("1xz0"), Radix::Binary), "1xz0");
+        assert_eq!(format_bits(&bits("101110"), Radix::Octal), "56");
+        assert_eq!(format_bits(&bits("10101111"), Radix::Hex), "af");
+        // Digits are taken from the least significant end.
+        assert_eq!(format_bits(&bits("1x0000"), Radix::Hex), "x0");
+        assert_eq!(format_bits(&bits("z1111"), Radix::Hex), "zf");
+        assert_eq!(format_bits(&bits("xz00"), Radix::Hex), "x");
+        // Too wide for decimal.
+        let wide = format!("1{}", "0".repeat(128));
+        assert_eq!(
+            format_bits(&bits(&wide), Radix::Unsigned),
+            format!("1{}", "0".repeat(32))
+        );
+    }
+
+    #[test]
+    fn padded_bits() {
+        let bit = |text: &str, width: usize| -> Vec<vcd::Value> {
+            (0..width)
+                .map(|pos| vector_bit(&vector(text), width, pos))
+                .collect()
+        };
+        assert_eq!(bit("101", 3), bits("101"));
+        assert_eq!(bit("1x", 4), bits("001x"));
+        assert_eq!(bit("x1", 4), bits("xxx1"));
+        assert_eq!(bit("z", 3), bits("zzz"));
+        assert_eq!(bit("0", 2), bits("00"));
+    }
+
+    fn vector_wave(index: Option<ReferenceIndex>) -> WaveData {
+        let data = ["0101", "0111", "1111"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                WaveChangePoint::new(i as SimTime * 10, WaveValue::Vector(vector(text)))
+            })
+            .collect();
+        WaveData {
+            size: 4,
+            index,
+            ..WaveData::new("w".to_string(), vec!["w".to_string()], data)
+        }
+    }
+
+    fn bit_names(wdata: &WaveData) -> Vec<String> {
+        wdata.split_bits().into_iter().map(|bit| bit.name).collect()
+    }
+
+    #[test]
+    fn descending_range() {
+        let wdata = vector_wave(Some(ReferenceIndex::Range(3, 0)));
+        assert_eq!(bit_names(&wdata), ["w[3]", "w[2]", "w[1]", "w[0]"]);
+        assert_eq!(wdata.bit_index(1), 2);
+        assert_eq!(wdata.bit_position(2), Some(1));
+        assert_eq!(wdata.bit_position(4), None);
+        assert_eq!(wdata.bit_position(-1), None);
+        let wdata = vector_wave(None);
+        assert_eq!(bit_names(&wdata), ["w[3]", "w[2]", "w[1]", "w[0]"]);
+        let wdata = vector_wave(Some(ReferenceIndex::Range(8, 5)));
+        assert_eq!(bit_names(&wdata), ["w[8]", "w[7]", "w[6]", "w[5]"]);
+        assert_eq!(wdata.bit_position(5), Some(3));
+    }
+
+    #[test]
+    fn ascending_range() {
+        let wdata = vector_wave(Some(ReferenceIndex::Range(0, 3)));
+        assert_eq!(bit_names(&wdata), ["w[0]", "w[1]", "w[2]", "w[3]"]);
+        assert_eq!(wdata.bit_index(1), 1);
+        assert_eq!(wdata.bit_position(3), Some(3));
+        assert_eq!(wdata.bit_position(4), None);
+    }
+
+    #[test]
+    fn from_bits_keeps_only_changes() {
+        let scalar = |time: SimTime, text: &str| {
+            WaveChangePoint::new(time, WaveValue::Scalar(bits(text)[0]))
+        };
+        let wave = |name: &str, data| WaveData::new(name.to_string(), vec![name.to_string()], data);
+        let msb = wave("a", vec![scalar(0, "0"), scalar(10, "1")]);
+        // A dump may write a bit again without changing it.
+        let lsb = wave("b", vec![scalar(5, "1"), scalar(10, "1"), scalar(20, "1")]);
+        let bus = WaveData::from_bits("bus".to_string(), &[&msb, &lsb]);
+        let changes: Vec<(SimTime, WaveValue)> = bus
+            .data
+            .iter()
+            .map(|cp| (cp.time, cp.value.clone()))
+            .collect();
+        let value = |text: &str| WaveValue::Vector(vector(text));
+        assert_eq!(
+            changes,
+            [(0, value("0x")), (5, value("01")), (10, value("11"))]
+        );
+    }
+
+    #[test]
+    fn split_bits_keeps_only_changes() {
+        let split = vector_wave(None).split_bits();
+        let changes = |pos: usize| -> Vec<(SimTime, WaveValue)> {
+            split[pos]
+                .data
+                .iter()
+                .map(|cp| (cp.time, cp.value.clone()))
+                .collect()
+        };
+        let scalar = |text: &str| WaveValue::Scalar(bits(text)[0]);
+        // The most significant bit rises at 20, bit 1 at 10, and bit 0 never changes.
+        assert_eq!(changes(0), [(0, scalar("0")), (20, scalar("1"))]);
+        assert_eq!(changes(2), [(0, scalar("0")), (10, scalar("1"))]);
+        assert_eq!(changes(3), [(0, scalar("1"))]);
+    }
 }